@@ -20,7 +20,12 @@ impl ParseCallbacks for Fixer {
             | "WEECHAT_HDATA_SHARED_STRING"
             | "WEECHAT_HDATA_LIST_CHECK_POINTERS"
             | "WEECHAT_RC_OK"
-            | "WEECHAT_RC_OK_EAT" => Some(IntKind::I32),
+            | "WEECHAT_RC_OK_EAT"
+            | "WEECHAT_CONFIG_READ_OK"
+            | "WEECHAT_CONFIG_WRITE_OK"
+            | "WEECHAT_CONFIG_OPTION_SET_OK_CHANGED"
+            | "WEECHAT_CONFIG_OPTION_SET_OK_SAME_VALUE"
+            | "WEECHAT_CONFIG_OPTION_SET_ERROR" => Some(IntKind::I32),
             _ => None,
         }
     }
@@ -0,0 +1,331 @@
+use std::fs::File;
+use std::io::Write;
+use std::ptr;
+use weechat::{
+    Buffer, CallResult, Error, Hashtable, HashtableValue, Hdata, HdataValue, Plugin, Result,
+};
+
+// A decoded hdata value. Pointers to other hdata are only followed as deep as
+// requested since lists link back to each other through prev and next
+enum Value {
+    Null,
+    Int(i64),
+    Str(String),
+    Ptr(usize),
+    Map(Vec<(String, String)>),
+    Array(Vec<Value>),
+    Hdata(usize, Option<Vec<Var>>),
+}
+
+struct Var {
+    name: String,
+    type_name: &'static str,
+    value: Option<Value>,
+}
+
+fn hashtable_value_string(value: &HashtableValue) -> String {
+    match *value {
+        HashtableValue::Integer(i) => i.to_string(),
+        HashtableValue::Str(ref s) => s.clone(),
+        HashtableValue::Pointer(p) => format!("0x{:x}", p as usize),
+        HashtableValue::Time(t) => t.to_string(),
+    }
+}
+
+fn decode_hashtable(hashtable: &Hashtable) -> Value {
+    let mut items: Vec<(String, String)> = hashtable
+        .iter()
+        .map(|(k, v)| (hashtable_value_string(&k), hashtable_value_string(&v)))
+        .collect();
+    items.sort();
+    Value::Map(items)
+}
+
+fn decode(value: HdataValue, depth: usize) -> Value {
+    match value {
+        HdataValue::None => Value::Null,
+        HdataValue::I8(v) => Value::Int(v as i64),
+        HdataValue::I32(v) => Value::Int(v as i64),
+        HdataValue::I64(v) => Value::Int(v),
+        HdataValue::Time(v) => Value::Int(v as i64),
+        HdataValue::Str(v) => Value::Str(v.to_string_lossy().into_owned()),
+        HdataValue::Ptr(p) | HdataValue::Other(p) => Value::Ptr(p as usize),
+        HdataValue::Hashtable(h) => decode_hashtable(&h),
+        HdataValue::Hdata(h) => {
+            let vars = if depth > 0 {
+                read_vars(&h, depth - 1).ok()
+            } else {
+                None
+            };
+            Value::Hdata(h.as_ptr() as usize, vars)
+        },
+    }
+}
+
+// Read all variables of an hdata. Without a data pointer only the names and
+// types are available
+fn read_vars(hdata: &Hdata, depth: usize) -> Result<Vec<Var>> {
+    // Following the links to neighbouring elements would expand them again at
+    // every level, so they are only shown as pointers
+    let links = [hdata.var_prev()?, hdata.var_next()?];
+
+    let mut vars = Vec::new();
+    for name in hdata.var_keys()? {
+        let value = if hdata.as_ptr().is_null() {
+            None
+        } else if links.contains(&Some(name)) {
+            Some(decode(hdata.get(name), 0))
+        } else if let Ok(values) = hdata.get_array(name) {
+            Some(Value::Array(
+                values.into_iter().map(|v| decode(v, depth)).collect(),
+            ))
+        } else {
+            Some(decode(hdata.get(name), depth))
+        };
+        vars.push(Var {
+            name: name.to_owned(),
+            type_name: hdata.get_type_name(name)?,
+            value,
+        });
+    }
+    Ok(vars)
+}
+
+fn format_value(value: &Value, indent: usize, lines: &mut Vec<String>) -> String {
+    match *value {
+        Value::Null => "null".to_owned(),
+        Value::Int(i) => i.to_string(),
+        Value::Str(ref s) => format!("{:?}", s),
+        Value::Ptr(p) => format!("0x{:x}", p),
+        Value::Map(ref items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|&(ref k, ref v)| format!("{:?}: {:?}", k, v))
+                .collect();
+            format!("{{{}}}", items.join(", "))
+        },
+        Value::Array(ref values) => {
+            let values: Vec<String> = values
+                .iter()
+                .map(|v| format_value(v, indent, lines))
+                .collect();
+            format!("[{}]", values.join(", "))
+        },
+        Value::Hdata(p, ref vars) => {
+            if let Some(ref vars) = *vars {
+                format_vars(vars, indent + 1, lines);
+            }
+            format!("0x{:x}", p)
+        },
+    }
+}
+
+fn format_vars(vars: &[Var], indent: usize, lines: &mut Vec<String>) {
+    for var in vars {
+        // Nested variables are collected separately so they end up below
+        // the pointer they belong to
+        let mut nested = Vec::new();
+        let line = match var.value {
+            Some(ref value) => format!(
+                "{}{} ({}): {}",
+                "  ".repeat(indent),
+                var.name,
+                var.type_name,
+                format_value(value, indent, &mut nested)
+            ),
+            None => format!("{}{} ({})", "  ".repeat(indent), var.name, var.type_name),
+        };
+        lines.push(line);
+        lines.append(&mut nested);
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn value_to_json(value: &Value) -> String {
+    match *value {
+        Value::Null => "null".to_owned(),
+        Value::Int(i) => i.to_string(),
+        Value::Str(ref s) => json_string(s),
+        Value::Ptr(p) | Value::Hdata(p, None) => json_string(&format!("0x{:x}", p)),
+        Value::Map(ref items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|&(ref k, ref v)| format!("{}:{}", json_string(k), json_string(v)))
+                .collect();
+            format!("{{{}}}", items.join(","))
+        },
+        Value::Array(ref values) => {
+            let values: Vec<String> = values.iter().map(value_to_json).collect();
+            format!("[{}]", values.join(","))
+        },
+        Value::Hdata(p, Some(ref vars)) => format!(
+            "{{\"__pointer\":{},{}}}",
+            json_string(&format!("0x{:x}", p)),
+            vars_to_json_fields(vars)
+        ),
+    }
+}
+
+fn vars_to_json_fields(vars: &[Var]) -> String {
+    let fields: Vec<String> = vars
+        .iter()
+        .map(|var| match var.value {
+            Some(ref value) => format!("{}:{}", json_string(&var.name), value_to_json(value)),
+            None => format!("{}:{}", json_string(&var.name), json_string(var.type_name)),
+        })
+        .collect();
+    fields.join(",")
+}
+
+struct DumpArgs<'a> {
+    name: &'a str,
+    list: Option<&'a str>,
+    depth: usize,
+    json: Option<&'a str>,
+}
+
+fn parse_args<'a>(args: &[&'a str]) -> Result<DumpArgs<'a>> {
+    let usage = || {
+        Error::Other("usage: /desktop hdata <name> [<list>] [-depth <n>] [-json <file>]".to_owned())
+    };
+    let mut dump_args = DumpArgs {
+        name: "",
+        list: None,
+        depth: 0,
+        json: None,
+    };
+
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        match arg {
+            "-depth" => {
+                dump_args.depth = args.next().and_then(|d| d.parse().ok()).ok_or_else(usage)?
+            },
+            "-json" => dump_args.json = Some(args.next().ok_or_else(usage)?),
+            _ if dump_args.name.is_empty() => dump_args.name = arg,
+            _ if dump_args.list.is_none() => dump_args.list = Some(arg),
+            _ => return Err(usage()),
+        }
+    }
+    if dump_args.name.is_empty() {
+        return Err(usage());
+    }
+    Ok(dump_args)
+}
+
+// Print every variable of an hdata type. When a list is given the values of
+// all elements in the list are printed as well, following pointers to other
+// hdata up to the given depth
+pub fn dump_hdata(plugin: &Plugin, buffer: &Buffer, args: &[&str]) -> CallResult {
+    let args = parse_args(args)?;
+
+    let elements = match args.list {
+        Some(list) => plugin
+            .hdata_from_list(args.name, list)?
+            .try_iter()?
+            .map(|h| read_vars(&h, args.depth))
+            .collect::<Result<Vec<_>>>()?,
+        None => vec![read_vars(
+            &plugin.hdata_from_ptr(args.name, ptr::null_mut())?,
+            0,
+        )?],
+    };
+
+    if let Some(path) = args.json {
+        let json: Vec<String> = elements
+            .iter()
+            .map(|vars| format!("{{{}}}", vars_to_json_fields(vars)))
+            .collect();
+        File::create(path)
+            .and_then(|mut f| f.write_all(format!("[{}]\n", json.join(",")).as_bytes()))
+            .map_err(|e| Error::Other(format!("failed to write {}: {}", path, e)))?;
+        buffer.print(&format!("hdata {} written to {}", args.name, path));
+        return Ok(());
+    }
+
+    for (i, vars) in elements.iter().enumerate() {
+        match args.list {
+            Some(list) => buffer.print(&format!("hdata {} ({}[{}]):", args.name, list, i)),
+            None => buffer.print(&format!("hdata {}:", args.name)),
+        }
+        let mut lines = Vec::new();
+        format_vars(vars, 1, &mut lines);
+        for line in lines {
+            buffer.print(&line);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_string, parse_args};
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a \"b\""), "\"a \\\"b\\\"\"");
+        assert_eq!(json_string("C:\\tmp"), "\"C:\\\\tmp\"");
+        assert_eq!(json_string("a\nb\tc\r"), "\"a\\nb\\tc\\r\"");
+        assert_eq!(json_string("\u{1}\u{1f}"), "\"\\u0001\\u001f\"");
+        assert_eq!(json_string("åäö"), "\"åäö\"");
+    }
+
+    #[test]
+    fn parse_args_all_options() {
+        let args =
+            parse_args(&["buffer", "gui_buffers", "-depth", "2", "-json", "out.json"]).unwrap();
+        assert_eq!(args.name, "buffer");
+        assert_eq!(args.list, Some("gui_buffers"));
+        assert_eq!(args.depth, 2);
+        assert_eq!(args.json, Some("out.json"));
+    }
+
+    #[test]
+    fn parse_args_defaults() {
+        let args = parse_args(&["buffer"]).unwrap();
+        assert_eq!(args.list, None);
+        assert_eq!(args.depth, 0);
+        assert_eq!(args.json, None);
+    }
+
+    #[test]
+    fn parse_args_requires_name() {
+        assert!(parse_args(&[]).is_err());
+        assert!(parse_args(&["-depth", "1"]).is_err());
+    }
+
+    #[test]
+    fn parse_args_invalid_depth() {
+        assert!(parse_args(&["buffer", "-depth"]).is_err());
+        assert!(parse_args(&["buffer", "-depth", "deep"]).is_err());
+        assert!(parse_args(&["buffer", "-depth", "-1"]).is_err());
+    }
+
+    #[test]
+    fn parse_args_json_requires_path() {
+        assert!(parse_args(&["buffer", "gui_buffers", "-json"]).is_err());
+    }
+
+    #[test]
+    fn parse_args_too_many_positional() {
+        assert!(parse_args(&["buffer", "gui_buffers", "extra"]).is_err());
+    }
+}
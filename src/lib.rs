@@ -1,21 +1,29 @@
+extern crate backtrace;
 #[macro_use]
 extern crate cfg_if;
 #[cfg(all(unix, not(target_os = "macos")))]
 extern crate dbus;
 extern crate libc;
 extern crate linkify;
-extern crate open;
 #[macro_use]
 extern crate weedesktop_macro;
 
+mod dump;
 mod ffi;
 mod platform;
 mod weechat;
 
 use linkify::{LinkFinder, LinkKind};
-use platform::screensaver_is_active;
+use platform::{screensaver_is_active, URL_OPENER};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 use std::time::Duration;
-use weechat::{Buffer, CallResult, Plugin};
+use weechat::{
+    Buffer, CallResult, Completion, CompletionPosition, ConfigOption, Error, FromHdata, Hashtable,
+    Hdata, InfolistRecord, Plugin, ProcessCommand, ProcessOutput,
+};
 
 #[plugin_info]
 pub static NAME: &str = "weedesktop";
@@ -28,25 +36,148 @@ pub static VERSION: &str = "0.1";
 #[plugin_info]
 pub static LICENSE: &str = "MIT";
 
+#[derive(FromHdata)]
+#[hdata(name = "irc_server")]
+struct IrcServer<'a> {
+    is_away: bool,
+    buffer: Option<Hdata<'a>>,
+}
+
+// The screensaver timer must be replaced when the poll interval changes
+static SCREENSAVER_TIMER: AtomicPtr<ffi::t_hook> = AtomicPtr::new(ptr::null_mut());
+
+// Failed screensaver checks are only reported once until a check succeeds, to
+// avoid printing an error on every poll
+static SCREENSAVER_ERROR_REPORTED: AtomicBool = AtomicBool::new(false);
+
+// State from the last successful poll, which infos are served from instead of
+// asking DBus on every evaluation. AUTO_AWAY is only set when we actually
+// marked a server as away
+static SCREEN_LOCKED: AtomicBool = AtomicBool::new(false);
+static AUTO_AWAY: AtomicBool = AtomicBool::new(false);
+
+// The irc plugin may be loaded after us, so the IrcServer mapping is checked
+// the first time the irc_server hdata exists. A mismatch disables auto-away
+static IRC_SERVER_CHECKED: AtomicBool = AtomicBool::new(false);
+static IRC_SERVER_VALID: AtomicBool = AtomicBool::new(false);
+
+fn config_init(plugin: &Plugin) -> CallResult {
+    let config = plugin.config_new("weedesktop")?;
+
+    let general = config.new_section("general")?;
+    general.new_boolean(
+        "auto_away",
+        "Set away status on all servers when the screen is locked",
+        true,
+        None,
+    )?;
+    general.new_string(
+        "away_message",
+        "Away message to use when the screen is locked",
+        "away",
+        None,
+    )?;
+    general.new_integer(
+        "poll_interval",
+        "Number of seconds between checks of the screen lock state",
+        1,
+        3600,
+        60,
+        Some(poll_interval_changed),
+    )?;
+    general.new_integer(
+        "url_scan_lines",
+        "Number of lines to search for URLs in /openurl",
+        1,
+        10000,
+        50,
+        None,
+    )?;
+
+    config.read()
+}
+
+fn hook_screensaver_timer(plugin: &Plugin) -> CallResult {
+    let interval = plugin
+        .config_get("weedesktop.general.poll_interval")?
+        .integer();
+    let hook = plugin.hook_timer(Duration::from_secs(interval as u64), 0, check_screensaver)?;
+
+    let old_hook = SCREENSAVER_TIMER.swap(hook, Ordering::SeqCst);
+    if !old_hook.is_null() {
+        plugin.unhook(old_hook);
+    }
+    Ok(())
+}
+
+fn poll_interval_changed(plugin: &Plugin, _option: &ConfigOption) -> CallResult {
+    hook_screensaver_timer(plugin)
+}
+
+fn irc_servers_usable(plugin: &Plugin) -> bool {
+    // Without the irc plugin there are simply no servers to set away on
+    if plugin
+        .hdata_from_ptr(IrcServer::HDATA_NAME, ptr::null_mut())
+        .is_err()
+    {
+        return false;
+    }
+    if !IRC_SERVER_CHECKED.swap(true, Ordering::SeqCst) {
+        match IrcServer::validate(plugin) {
+            Ok(()) => IRC_SERVER_VALID.store(true, Ordering::SeqCst),
+            Err(e) => plugin.print_error(&Error::Other(format!(
+                "auto-away disabled, irc plugin is not supported: {}",
+                e
+            ))),
+        }
+    }
+    IRC_SERVER_VALID.load(Ordering::SeqCst)
+}
+
 fn check_screensaver(plugin: &Plugin, _remaining_calls: i32) -> CallResult {
     let screensaver_on = match screensaver_is_active() {
-        Ok(is_on) => is_on,
-        Err(_) => return Ok(()),
+        Ok(screensaver_on) => {
+            SCREENSAVER_ERROR_REPORTED.store(false, Ordering::SeqCst);
+            screensaver_on
+        },
+        Err(e) => {
+            if !SCREENSAVER_ERROR_REPORTED.swap(true, Ordering::SeqCst) {
+                plugin.print_error(&e);
+            }
+            return Ok(());
+        },
     };
+    SCREEN_LOCKED.store(screensaver_on, Ordering::SeqCst);
+    if !screensaver_on {
+        AUTO_AWAY.store(false, Ordering::SeqCst);
+    }
 
-    for irc_server in plugin
-        .hdata_from_list("irc_server", "irc_servers")?
-        .try_iter()?
+    if !plugin.config_get("weedesktop.general.auto_away")?.boolean() || !irc_servers_usable(plugin)
     {
-        let is_away = match irc_server.get_i32("is_away") {
-            Ok(away) => away != 0,
-            Err(_) => continue,
-        };
-        if let Ok(buffer_hdata) = irc_server.get_hdata("buffer") {
+        return Ok(());
+    }
+    let away_command = format!(
+        "/away {}",
+        plugin
+            .config_get("weedesktop.general.away_message")?
+            .string()?
+    );
+
+    let irc_servers = match plugin.hdata_from_list(IrcServer::HDATA_NAME, "irc_servers") {
+        Ok(irc_servers) => irc_servers,
+        // There are no servers
+        Err(Error::NotFound(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for irc_server in irc_servers.try_iter()? {
+        let server: IrcServer = irc_server.parse()?;
+        if let Some(buffer_hdata) = server.buffer {
             let buffer = Buffer::try_from_hdata(buffer_hdata)?;
-            if !is_away && screensaver_on {
-                buffer.command("/away away").ok();
-            } else if is_away && !screensaver_on {
+            if !server.is_away && screensaver_on {
+                if buffer.command(&away_command).is_ok() {
+                    AUTO_AWAY.store(true, Ordering::SeqCst);
+                }
+            } else if server.is_away && !screensaver_on {
                 // Remove away status
                 buffer.command("/away").ok();
             }
@@ -55,26 +186,233 @@ fn check_screensaver(plugin: &Plugin, _remaining_calls: i32) -> CallResult {
     Ok(())
 }
 
-fn open_url(_plugin: &Plugin, buffer: Buffer, _cmd: &str, _args: Vec<&str>) -> CallResult {
-    for line in buffer.iter_lines_from_bottom()?.take(50) {
-        if let Some(link) = LinkFinder::new().kinds(&[LinkKind::Url]).links(line).next() {
-            open::that(link.as_str()).or(Err(()))?;
-            break;
+// Errors are printed to the buffer the URL was taken from, unless it has been
+// closed while the opener was running
+fn open_url_done(plugin: &Plugin, buffer_name: &str, output: ProcessOutput) -> CallResult {
+    if output.return_code == Some(0) {
+        return Ok(());
+    }
+    let err = Error::Other(format!(
+        "failed to open URL using {}: {}",
+        URL_OPENER,
+        output.stderr.trim()
+    ));
+    match plugin.buffer_search_by_full_name(buffer_name)? {
+        Some(buffer) => {
+            buffer.print_error(&err);
+            Ok(())
+        },
+        None => Err(err),
+    }
+}
+
+fn recent_urls(plugin: &Plugin, buffer: &Buffer) -> weechat::Result<Vec<String>> {
+    let scan_lines = plugin
+        .config_get("weedesktop.general.url_scan_lines")?
+        .integer();
+
+    let mut urls: Vec<String> = Vec::new();
+    for line in buffer.iter_lines_from_bottom()?.take(scan_lines as usize) {
+        for link in LinkFinder::new()
+            .kinds(&[LinkKind::Url])
+            .links(line.message)
+        {
+            if !urls.iter().any(|u| u == link.as_str()) {
+                urls.push(link.as_str().to_owned());
+            }
         }
     }
+    Ok(urls)
+}
+
+fn open_url(plugin: &Plugin, buffer: Buffer, _cmd: &str, args: Vec<&str>) -> CallResult {
+    // URLs are taken from the current buffer unless another one is given
+    let (buffer, args) = match args.split_first() {
+        Some((&"-buffer", rest)) => {
+            let name = rest
+                .first()
+                .ok_or_else(|| Error::Other("-buffer requires a buffer name".to_owned()))?;
+            let buffer = plugin
+                .buffer_search_by_full_name(name)?
+                .ok_or_else(|| Error::NotFound(format!("buffer {}", name)))?;
+            (buffer, &rest[1..])
+        },
+        _ => (buffer, &args[..]),
+    };
+
+    // The argument may be either a URL or the index of a recent URL, where 1
+    // is the most recent one
+    let url = match args.first() {
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(i) if i > 0 => recent_urls(plugin, &buffer)?.into_iter().nth(i - 1),
+            _ => Some(arg.to_string()),
+        },
+        None => recent_urls(plugin, &buffer)?.into_iter().next(),
+    };
+
+    if let Some(url) = url {
+        // Run the opener asynchronously to avoid blocking Weechat
+        let process = ProcessCommand::new(URL_OPENER)
+            .arg(&url)
+            .timeout(Duration::from_secs(30));
+        let buffer_name = buffer.get_full_name()?.to_owned();
+        plugin.run_process(&process, move |plugin, output| {
+            open_url_done(plugin, &buffer_name, output)
+        })?;
+    }
+    Ok(())
+}
+
+fn desktop(plugin: &Plugin, buffer: Buffer, _cmd: &str, args: Vec<&str>) -> CallResult {
+    match args.split_first() {
+        Some((&"hdata", args)) => dump::dump_hdata(plugin, &buffer, args),
+        _ => Err(Error::Other(
+            "unknown subcommand, see /help desktop".to_owned(),
+        )),
+    }
+}
+
+fn complete_urls(
+    plugin: &Plugin,
+    _item: &str,
+    buffer: Buffer,
+    completion: &Completion,
+) -> CallResult {
+    // Keep the most recent URL first instead of sorting alphabetically
+    for url in recent_urls(plugin, &buffer)? {
+        completion.add(&url, false, CompletionPosition::End)?;
+    }
     Ok(())
 }
 
+fn cached_flag(flag: &AtomicBool) -> String {
+    if flag.load(Ordering::SeqCst) {
+        "1"
+    } else {
+        "0"
+    }
+    .to_owned()
+}
+
+fn info_locked(_plugin: &Plugin, _name: &str, _args: &str) -> weechat::Result<Option<String>> {
+    Ok(Some(cached_flag(&SCREEN_LOCKED)))
+}
+
+fn info_auto_away(_plugin: &Plugin, _name: &str, _args: &str) -> weechat::Result<Option<String>> {
+    Ok(Some(cached_flag(&AUTO_AWAY)))
+}
+
+fn info_status(
+    plugin: &Plugin,
+    _name: &str,
+    _args: &Hashtable,
+) -> weechat::Result<HashMap<String, String>> {
+    let mut status = HashMap::new();
+    status.insert("locked".to_owned(), cached_flag(&SCREEN_LOCKED));
+    status.insert("auto_away".to_owned(), cached_flag(&AUTO_AWAY));
+    status.insert(
+        "away_message".to_owned(),
+        plugin
+            .config_get("weedesktop.general.away_message")?
+            .string()?,
+    );
+    Ok(status)
+}
+
+fn infolist_urls(
+    plugin: &Plugin,
+    _name: &str,
+    pointer: *mut c_void,
+    _args: &str,
+) -> weechat::Result<Vec<InfolistRecord>> {
+    let buffer = if pointer.is_null() {
+        plugin
+            .current_buffer()
+            .ok_or(Error::NullPointer("buffer_search"))?
+    } else {
+        Buffer::try_from_hdata(plugin.hdata_from_ptr("buffer", pointer)?)?
+    };
+
+    // Indexes match the ones accepted by /openurl
+    Ok(recent_urls(plugin, &buffer)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, url)| {
+            let mut record = InfolistRecord::new();
+            record.set("index", i as i32 + 1);
+            record.set("url", url);
+            record
+        })
+        .collect())
+}
+
 #[plugin_init]
 fn init(plugin: &Plugin) -> CallResult {
-    plugin.hook_timer(Duration::from_secs(60), 0, check_screensaver)?;
+    config_init(plugin)?;
+    hook_screensaver_timer(plugin)?;
+    plugin.hook_completion(
+        "weedesktop_urls",
+        "Recent URLs in the current buffer",
+        complete_urls,
+    )?;
     plugin.hook_command(
         "openurl",
         "Opens the most recent URL in the current buffer",
+        "[-buffer <name>] [<url>|<index>]",
+        "buffer: full name of the buffer to take URLs from instead of the current \
+         one\n   url: URL to open\n index: open the n:th most recent URL, starting from 1",
+        "-buffer %(buffers_plugins_names) %(weedesktop_urls) || %(weedesktop_urls)",
+        open_url,
+    )?;
+    plugin.hook_command(
+        "desktop",
+        "Desktop integration utilities",
+        "hdata <name> [<list>] [-depth <n>] [-json <file>]",
+        " hdata: print the variables of an hdata type, including values for all \
+         elements when a list is given\n\
+         depth: how many levels of pointers to other hdata to follow\n\
+          json: write the result as JSON to the given file instead",
+        "hdata",
+        desktop,
+    )?;
+    plugin.hook_info(
+        "weedesktop_locked",
+        "1 if the screen was locked at the last poll, otherwise 0",
         None,
+        info_locked,
+    )?;
+    plugin.hook_info(
+        "weedesktop_auto_away",
+        "1 if servers were marked as away because the screen is locked, otherwise 0",
         None,
+        info_auto_away,
+    )?;
+    plugin.hook_info_hashtable(
+        "weedesktop_status",
+        "Desktop state",
         None,
-        open_url,
+        "\"locked\", \"auto_away\" and \"away_message\"",
+        info_status,
+    )?;
+    plugin.hook_infolist(
+        "weedesktop_urls",
+        "Recent URLs in a buffer, most recent first",
+        "buffer pointer (optional, defaults to the current buffer)",
+        None,
+        infolist_urls,
     )?;
     Ok(())
 }
+
+#[plugin_end]
+fn end(plugin: &Plugin) -> CallResult {
+    // Weechat removes all our hooks when unloading the plugin
+    SCREENSAVER_TIMER.store(ptr::null_mut(), Ordering::SeqCst);
+    IRC_SERVER_CHECKED.store(false, Ordering::SeqCst);
+    IRC_SERVER_VALID.store(false, Ordering::SeqCst);
+
+    let config = plugin.config_search("weedesktop")?;
+    let result = config.write();
+    config.free();
+    result
+}
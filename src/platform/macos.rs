@@ -1,5 +1,7 @@
 use weechat::Result;
 
+pub static URL_OPENER: &str = "open";
+
 pub fn screensaver_is_active() -> Result<bool> {
     // TODO: Implement this
     Ok(false)
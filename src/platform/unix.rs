@@ -1,15 +1,30 @@
 use dbus::{BusType, Connection, Message};
-use weechat::Result;
+use weechat::{Error, Result};
+
+pub static URL_OPENER: &str = "xdg-open";
+
+static SCREENSAVER_CALL: &str = "org.gnome.ScreenSaver.GetActive";
+
+fn dbus_error<E: ToString>(err: E) -> Error {
+    Error::Dbus {
+        call: SCREENSAVER_CALL.to_owned(),
+        message: err.to_string(),
+    }
+}
 
 pub fn screensaver_is_active() -> Result<bool> {
-    let conn = Connection::get_private(BusType::Session).or(Err(()))?;
+    let conn = Connection::get_private(BusType::Session).map_err(dbus_error)?;
     let msg = Message::new_method_call(
         "org.gnome.ScreenSaver",
         "/org/gnome/ScreenSaver",
         "org.gnome.ScreenSaver",
         "GetActive",
-    ).or(Err(()))?;
+    )
+    .map_err(dbus_error)?;
 
-    let resp = conn.send_with_reply_and_block(msg, 100).or(Err(()))?;
-    Ok(resp.get1().ok_or(())?)
+    let resp = conn
+        .send_with_reply_and_block(msg, 100)
+        .map_err(dbus_error)?;
+    resp.get1()
+        .ok_or_else(|| dbus_error("response did not contain a boolean"))
 }
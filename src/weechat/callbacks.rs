@@ -1,28 +1,203 @@
-use super::{Buffer, CallResult, Plugin, Result};
-use std::ffi::{c_void, CStr};
+use super::infolist::infolist_from_records;
+use super::panic::catch_panic;
+use super::{
+    Buffer, CallResult, Completion, ConfigOption, Error, Hashtable, Hook, InfolistRecord, Plugin,
+    ProcessOutput, Result,
+};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::rc::Rc;
 
 macro_rules! try_unwrap {
-    ($expr:expr) => {
+    ($plugin:expr, $expr:expr) => {
         match $expr {
             Ok(x) => x,
-            Err(_) => return ::ffi::WEECHAT_RC_ERROR,
+            Err(e) => return to_return_code($plugin, Err(e.into())),
         }
     };
 }
 
 pub type CommandHook = fn(&Plugin, buffer: Buffer, cmd: &str, args: Vec<&str>) -> CallResult;
 pub type TimerHook = fn(&Plugin, i32) -> CallResult;
+// Called with the modifier, modifier data and string to modify
+pub type ModifierHook = dyn FnMut(&Plugin, &str, &str, &str) -> Result<String>;
+pub type ProcessHook = dyn FnMut(&Plugin, ProcessOutput) -> CallResult;
+pub type FdHook = dyn FnMut(&Plugin, RawFd) -> CallResult;
+pub type CompletionHook =
+    fn(&Plugin, item: &str, buffer: Buffer, completion: &Completion) -> CallResult;
+pub type ConfigChangeHook = fn(&Plugin, option: &ConfigOption) -> CallResult;
+pub type BufferInputHook = dyn FnMut(&Plugin, Buffer, &str) -> CallResult;
+pub type BufferCloseHook = dyn FnMut(&Plugin, Buffer) -> CallResult;
+pub type InfoHook = fn(&Plugin, name: &str, args: &str) -> Result<Option<String>>;
+pub type InfoHashtableHook =
+    fn(&Plugin, name: &str, args: &Hashtable) -> Result<HashMap<String, String>>;
+pub type InfolistHook =
+    fn(&Plugin, name: &str, pointer: *mut c_void, args: &str) -> Result<Vec<InfolistRecord>>;
+
+// State for callbacks that own heap data. Weechat releases callback data using
+// free(), which would leak whatever the data owns, so the state is passed as
+// callback pointer instead and kept alive in HOOK_STATES until it is dropped
+pub struct HookState<T> {
+    plugin: *mut ::ffi::t_weechat_plugin,
+    data: T,
+}
+
+thread_local! {
+    // States by address, along with the hook using them once it is created
+    static HOOK_STATES: RefCell<HashMap<usize, (Hook, Rc<dyn Any>)>> =
+        RefCell::new(HashMap::new());
+}
+
+// Store the state and get the pointer to pass to Weechat. This must be done
+// before creating the hook since Weechat may run the callback right away
+pub fn keep_state<T: 'static>(plugin: &Plugin, data: T) -> *const c_void {
+    let state = Rc::new(HookState {
+        plugin: plugin.ptr,
+        data,
+    });
+    let state_ptr = &*state as *const HookState<T> as *const c_void;
+    let state = state as Rc<dyn Any>;
+    HOOK_STATES.with(|s| {
+        s.borrow_mut()
+            .insert(state_ptr as usize, (ptr::null_mut(), state));
+    });
+    state_ptr
+}
+
+// Associate the state with the hook created for it, so it is dropped when
+// unhooking. The state is dropped right away if the hook failed
+pub fn attach_state(state_ptr: *const c_void, hook: Hook, call: &'static str) -> Result<Hook> {
+    if hook.is_null() {
+        drop_state(state_ptr);
+        return Err(Error::NullPointer(call));
+    }
+    HOOK_STATES.with(|s| {
+        if let Some(entry) = s.borrow_mut().get_mut(&(state_ptr as usize)) {
+            entry.0 = hook;
+        }
+    });
+    Ok(hook)
+}
+
+pub fn drop_state(state_ptr: *const c_void) {
+    // The state is dropped after releasing the borrow, since dropping it may
+    // run arbitrary destructors
+    let state = HOOK_STATES.with(|s| s.borrow_mut().remove(&(state_ptr as usize)));
+    drop(state);
+}
+
+pub fn drop_hook_states(hook: Hook) {
+    let states: Vec<_> = HOOK_STATES.with(|s| {
+        let mut s = s.borrow_mut();
+        let keys: Vec<usize> = s
+            .iter()
+            .filter(|&(_, &(h, _))| h == hook)
+            .map(|(k, _)| *k)
+            .collect();
+        keys.into_iter().filter_map(|k| s.remove(&k)).collect()
+    });
+    drop(states);
+}
+
+// Run f with the state Weechat passed to a callback. We hold a reference for
+// the duration of the call, so callbacks may remove their own hook
+fn with_state<T, R, F>(state_ptr: *const c_void, fallback: R, f: F) -> R
+where
+    T: 'static,
+    R: Copy,
+    F: FnOnce(&Plugin, &T) -> R,
+{
+    let state = HOOK_STATES.with(|s| {
+        s.borrow()
+            .get(&(state_ptr as usize))
+            .map(|&(_, ref state)| state.clone())
+    });
+    let state = match state.and_then(|s| s.downcast::<HookState<T>>().ok()) {
+        Some(state) => state,
+        None => return fallback,
+    };
+
+    let plugin = Plugin::new(state.plugin);
+    catch_panic(&plugin, fallback, || f(&plugin, &state.data))
+}
+
+pub struct ProcessState {
+    callback: Box<ProcessHook>,
+    stdout: String,
+    stderr: String,
+}
+
+impl ProcessState {
+    pub fn new(callback: Box<ProcessHook>) -> Self {
+        Self {
+            callback,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+}
+
+// Input and close callbacks of a buffer share the same state, which is dropped
+// once the buffer has been closed
+pub struct BufferState {
+    input: RefCell<Option<Box<BufferInputHook>>>,
+    close: RefCell<Option<Box<BufferCloseHook>>>,
+}
+
+impl BufferState {
+    pub fn new(input: Option<Box<BufferInputHook>>, close: Option<Box<BufferCloseHook>>) -> Self {
+        Self {
+            input: RefCell::new(input),
+            close: RefCell::new(close),
+        }
+    }
+}
+
+pub struct InfoState {
+    callback: InfoHook,
+    value: Option<CString>,
+}
+
+impl InfoState {
+    pub fn new(callback: InfoHook) -> Self {
+        Self {
+            callback,
+            value: None,
+        }
+    }
+}
+
+// Convert the result of a callback into a Weechat return code. Since Weechat
+// discards errors silently we print them to the core buffer
+fn to_return_code(plugin: &Plugin, result: CallResult) -> i32 {
+    match result {
+        Ok(_) => ::ffi::WEECHAT_RC_OK,
+        Err(e) => {
+            plugin.print_error(&e);
+            ::ffi::WEECHAT_RC_ERROR
+        },
+    }
+}
 
 pub fn malloc_callback<T>(callback: T) -> Result<*mut T> {
     // Allocate a blob big enough to hold a pointer to a function. This will be
     // used to allow hook_timer_callback to dispatch the callback to the given
     // TimerHook. We must use malloc since Weechat will automatically free the
     // pointer we give when the plugin is tearing down
-    let callback_ptr = try_ptr!(unsafe { libc::malloc(std::mem::size_of::<T>()) as *mut T });
+    let callback_ptr = try_ptr!(
+        unsafe { libc::malloc(std::mem::size_of::<T>()) as *mut T },
+        "malloc"
+    );
 
-    // Assign function pointer to the datablob that is sent to the callback hook
+    // Assign function pointer to the datablob that is sent to the callback
+    // hook. The memory is uninitialized so we must not drop the old value
     unsafe {
-        *callback_ptr = callback;
+        ptr::write(callback_ptr, callback);
     }
 
     Ok(callback_ptr)
@@ -41,24 +216,25 @@ pub extern "C" fn hook_command(
     }
 
     let plugin = Plugin::new(ptr as *mut ::ffi::t_weechat_plugin);
-    let hdata = try_unwrap!(plugin.hdata_from_ptr("buffer", buffer as *mut c_void));
-    let buffer = try_unwrap!(Buffer::try_from_hdata(hdata));
-    let cmd = try_unwrap!(unsafe { CStr::from_ptr(*argv).to_str() });
-
-    // Since the first arg is the command name we start at 1 here
-    let mut args: Vec<&str> = Vec::with_capacity((argc - 1) as usize);
-    for i in 1..(argc as isize) {
-        match unsafe { CStr::from_ptr(*argv.offset(i)).to_str() } {
-            Ok(s) => args.push(s),
-            Err(_) => return ::ffi::WEECHAT_RC_ERROR,
-        };
-    }
+    catch_panic(&plugin, ::ffi::WEECHAT_RC_ERROR, || {
+        let hdata = try_unwrap!(
+            &plugin,
+            plugin.hdata_from_ptr("buffer", buffer as *mut c_void)
+        );
+        let buffer = try_unwrap!(&plugin, Buffer::try_from_hdata(hdata));
+        let cmd = try_unwrap!(&plugin, unsafe { CStr::from_ptr(*argv).to_str() });
 
-    let callback = unsafe { *(data as *mut CommandHook) };
-    match callback(&plugin, buffer, cmd, args) {
-        Ok(_) => ::ffi::WEECHAT_RC_OK,
-        Err(_) => ::ffi::WEECHAT_RC_ERROR,
-    }
+        // Since the first arg is the command name we start at 1 here
+        let mut args: Vec<&str> = Vec::with_capacity((argc - 1) as usize);
+        for i in 1..(argc as isize) {
+            args.push(try_unwrap!(&plugin, unsafe {
+                CStr::from_ptr(*argv.offset(i)).to_str()
+            }));
+        }
+
+        let callback = unsafe { *(data as *mut CommandHook) };
+        to_return_code(&plugin, callback(&plugin, buffer, cmd, args))
+    })
 }
 
 pub extern "C" fn hook_timer(ptr: *const c_void, data: *mut c_void, remaining_calls: i32) -> i32 {
@@ -66,12 +242,328 @@ pub extern "C" fn hook_timer(ptr: *const c_void, data: *mut c_void, remaining_ca
         return ::ffi::WEECHAT_RC_ERROR;
     }
 
-    let callback = unsafe { *(data as *mut TimerHook) };
-    match callback(
-        &Plugin::new(ptr as *mut ::ffi::t_weechat_plugin),
-        remaining_calls,
-    ) {
-        Ok(_) => ::ffi::WEECHAT_RC_OK,
-        Err(_) => ::ffi::WEECHAT_RC_ERROR,
+    let plugin = Plugin::new(ptr as *mut ::ffi::t_weechat_plugin);
+    catch_panic(&plugin, ::ffi::WEECHAT_RC_ERROR, || {
+        let callback = unsafe { *(data as *mut TimerHook) };
+        to_return_code(&plugin, callback(&plugin, remaining_calls))
+    })
+}
+
+pub extern "C" fn hook_modifier(
+    ptr: *const c_void,
+    _data: *mut c_void,
+    modifier: *const c_char,
+    modifier_data: *const c_char,
+    string: *const c_char,
+) -> *mut c_char {
+    // Returning a null pointer tells Weechat to leave the string unchanged
+    if modifier.is_null() || string.is_null() {
+        return ptr::null_mut();
+    }
+
+    with_state(
+        ptr,
+        ptr::null_mut(),
+        |plugin, callback: &RefCell<Box<ModifierHook>>| {
+            let result = (|| -> Result<CString> {
+                let modifier = unsafe { CStr::from_ptr(modifier) }.to_str()?;
+                let modifier_data = if modifier_data.is_null() {
+                    ""
+                } else {
+                    unsafe { CStr::from_ptr(modifier_data) }.to_str()?
+                };
+                let string = unsafe { CStr::from_ptr(string) }.to_str()?;
+                let mut callback = callback.borrow_mut();
+                Ok(CString::new((&mut **callback)(
+                    plugin,
+                    modifier,
+                    modifier_data,
+                    string,
+                )?)?)
+            })();
+
+            // Weechat takes ownership of the returned string and will free it
+            match result {
+                Ok(s) => unsafe { libc::strdup(s.as_ptr()) },
+                Err(e) => {
+                    plugin.print_error(&e);
+                    ptr::null_mut()
+                },
+            }
+        },
+    )
+}
+
+pub extern "C" fn hook_process(
+    ptr: *const c_void,
+    _data: *mut c_void,
+    command: *const c_char,
+    return_code: i32,
+    out: *const c_char,
+    err: *const c_char,
+) -> i32 {
+    with_state(
+        ptr,
+        ::ffi::WEECHAT_RC_ERROR,
+        |plugin, state: &RefCell<ProcessState>| {
+            // Weechat calls us repeatedly with chunks of output while the
+            // process is running, so we buffer everything until it has ended
+            let mut state = state.borrow_mut();
+            if !out.is_null() {
+                state
+                    .stdout
+                    .push_str(&unsafe { CStr::from_ptr(out) }.to_string_lossy());
+            }
+            if !err.is_null() {
+                state
+                    .stderr
+                    .push_str(&unsafe { CStr::from_ptr(err) }.to_string_lossy());
+            }
+
+            if return_code == ::ffi::WEECHAT_HOOK_PROCESS_RUNNING {
+                return ::ffi::WEECHAT_RC_OK;
+            }
+
+            // Weechat removes the hook after the last call without telling us
+            drop_state(ptr);
+
+            let output = ProcessOutput {
+                command: if command.is_null() {
+                    String::new()
+                } else {
+                    unsafe { CStr::from_ptr(command) }
+                        .to_string_lossy()
+                        .into_owned()
+                },
+                return_code: if return_code >= 0 {
+                    Some(return_code)
+                } else {
+                    None
+                },
+                stdout: std::mem::replace(&mut state.stdout, String::new()),
+                stderr: std::mem::replace(&mut state.stderr, String::new()),
+            };
+
+            to_return_code(plugin, (&mut *state.callback)(plugin, output))
+        },
+    )
+}
+
+pub extern "C" fn hook_fd(ptr: *const c_void, _data: *mut c_void, fd: i32) -> i32 {
+    with_state(
+        ptr,
+        ::ffi::WEECHAT_RC_ERROR,
+        |plugin, callback: &RefCell<Box<FdHook>>| {
+            let mut callback = callback.borrow_mut();
+            to_return_code(plugin, (&mut **callback)(plugin, fd))
+        },
+    )
+}
+
+pub extern "C" fn hook_completion(
+    ptr: *const c_void,
+    data: *mut c_void,
+    completion_item: *const c_char,
+    buffer: *mut ::ffi::t_gui_buffer,
+    completion: *mut ::ffi::t_gui_completion,
+) -> i32 {
+    if data.is_null() || completion_item.is_null() || completion.is_null() {
+        return ::ffi::WEECHAT_RC_ERROR;
+    }
+
+    let plugin = Plugin::new(ptr as *mut ::ffi::t_weechat_plugin);
+    catch_panic(&plugin, ::ffi::WEECHAT_RC_ERROR, || {
+        let hdata = try_unwrap!(
+            &plugin,
+            plugin.hdata_from_ptr("buffer", buffer as *mut c_void)
+        );
+        let buffer = try_unwrap!(&plugin, Buffer::try_from_hdata(hdata));
+        let item = try_unwrap!(&plugin, unsafe { CStr::from_ptr(completion_item).to_str() });
+
+        let callback = unsafe { *(data as *mut CompletionHook) };
+        let completion = Completion::new(&plugin, completion);
+        to_return_code(&plugin, callback(&plugin, item, buffer, &completion))
+    })
+}
+
+pub extern "C" fn config_option_change(
+    ptr: *const c_void,
+    data: *mut c_void,
+    option: *mut ::ffi::t_config_option,
+) {
+    if data.is_null() || option.is_null() {
+        return;
     }
+
+    // Weechat ignores the result of change callbacks, so we can only print
+    // errors here
+    let plugin = Plugin::new(ptr as *mut ::ffi::t_weechat_plugin);
+    catch_panic(&plugin, (), || {
+        let callback = unsafe { *(data as *mut ConfigChangeHook) };
+        to_return_code(
+            &plugin,
+            callback(&plugin, &ConfigOption::new(&plugin, option)),
+        );
+    })
+}
+
+pub extern "C" fn buffer_input(
+    ptr: *const c_void,
+    _data: *mut c_void,
+    buffer: *mut ::ffi::t_gui_buffer,
+    input_data: *const c_char,
+) -> i32 {
+    if input_data.is_null() {
+        return ::ffi::WEECHAT_RC_ERROR;
+    }
+
+    with_state(
+        ptr,
+        ::ffi::WEECHAT_RC_ERROR,
+        |plugin, state: &BufferState| {
+            let hdata = try_unwrap!(
+                plugin,
+                plugin.hdata_from_ptr("buffer", buffer as *mut c_void)
+            );
+            let buffer = try_unwrap!(plugin, Buffer::try_from_hdata(hdata));
+            let input = try_unwrap!(plugin, unsafe { CStr::from_ptr(input_data).to_str() });
+
+            match *state.input.borrow_mut() {
+                Some(ref mut callback) => {
+                    to_return_code(plugin, (&mut **callback)(plugin, buffer, input))
+                },
+                None => ::ffi::WEECHAT_RC_OK,
+            }
+        },
+    )
+}
+
+pub extern "C" fn buffer_close(
+    ptr: *const c_void,
+    _data: *mut c_void,
+    buffer: *mut ::ffi::t_gui_buffer,
+) -> i32 {
+    // The buffer is still valid here, Weechat closes it after we return
+    let rc = with_state(
+        ptr,
+        ::ffi::WEECHAT_RC_ERROR,
+        |plugin, state: &BufferState| {
+            let hdata = try_unwrap!(
+                plugin,
+                plugin.hdata_from_ptr("buffer", buffer as *mut c_void)
+            );
+            let buffer = try_unwrap!(plugin, Buffer::try_from_hdata(hdata));
+
+            match *state.close.borrow_mut() {
+                Some(ref mut callback) => to_return_code(plugin, (&mut **callback)(plugin, buffer)),
+                None => ::ffi::WEECHAT_RC_OK,
+            }
+        },
+    );
+
+    // Neither callback can be called after this, so the closures are dropped
+    drop_state(ptr);
+    rc
+}
+
+fn str_or_empty<'a>(ptr: *const c_char) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Ok("");
+    }
+    Ok(unsafe { CStr::from_ptr(ptr) }.to_str()?)
+}
+
+pub extern "C" fn hook_info(
+    ptr: *const c_void,
+    _data: *mut c_void,
+    info_name: *const c_char,
+    arguments: *const c_char,
+) -> *const c_char {
+    with_state(ptr, ptr::null(), |plugin, state: &RefCell<InfoState>| {
+        let mut state = state.borrow_mut();
+        let result = (|| -> Result<Option<CString>> {
+            let name = str_or_empty(info_name)?;
+            let args = str_or_empty(arguments)?;
+            match (state.callback)(plugin, name, args)? {
+                Some(value) => Ok(Some(CString::new(value)?)),
+                None => Ok(None),
+            }
+        })();
+
+        // Weechat does not take ownership of the returned string, so we keep
+        // it alive until the next call
+        state.value = match result {
+            Ok(value) => value,
+            Err(e) => {
+                plugin.print_error(&e);
+                None
+            },
+        };
+        state.value.as_ref().map_or(ptr::null(), |v| v.as_ptr())
+    })
+}
+
+pub extern "C" fn hook_info_hashtable(
+    ptr: *const c_void,
+    data: *mut c_void,
+    info_name: *const c_char,
+    hashtable: *mut ::ffi::t_hashtable,
+) -> *mut ::ffi::t_hashtable {
+    if data.is_null() || hashtable.is_null() {
+        return ptr::null_mut();
+    }
+
+    let plugin = Plugin::new(ptr as *mut ::ffi::t_weechat_plugin);
+    catch_panic(&plugin, ptr::null_mut(), || {
+        let callback = unsafe { *(data as *mut InfoHashtableHook) };
+        let result = (|| -> Result<*mut ::ffi::t_hashtable> {
+            let name = str_or_empty(info_name)?;
+            let args = Hashtable::from_ptr(&plugin, hashtable)?;
+            let map = callback(&plugin, name, &args)?;
+
+            // Weechat frees the returned hashtable
+            Ok(plugin.hashtable_from_map(&map)?.into_raw())
+        })();
+
+        match result {
+            Ok(hashtable) => hashtable,
+            Err(e) => {
+                plugin.print_error(&e);
+                ptr::null_mut()
+            },
+        }
+    })
+}
+
+pub extern "C" fn hook_infolist(
+    ptr: *const c_void,
+    data: *mut c_void,
+    infolist_name: *const c_char,
+    obj_pointer: *mut c_void,
+    arguments: *const c_char,
+) -> *mut ::ffi::t_infolist {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+
+    let plugin = Plugin::new(ptr as *mut ::ffi::t_weechat_plugin);
+    catch_panic(&plugin, ptr::null_mut(), || {
+        let callback = unsafe { *(data as *mut InfolistHook) };
+        let result = (|| -> Result<*mut ::ffi::t_infolist> {
+            let name = str_or_empty(infolist_name)?;
+            let args = str_or_empty(arguments)?;
+            let records = callback(&plugin, name, obj_pointer, args)?;
+
+            // Weechat frees the returned infolist
+            infolist_from_records(&plugin, &records)
+        })();
+
+        match result {
+            Ok(infolist) => infolist,
+            Err(e) => {
+                plugin.print_error(&e);
+                ptr::null_mut()
+            },
+        }
+    })
 }
@@ -0,0 +1,81 @@
+use super::{Plugin, Result};
+use std::ffi::{CStr, CString};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionPosition {
+    Sort,
+    Beginning,
+    End,
+}
+
+#[derive(Debug)]
+pub struct Completion<'a> {
+    plugin: &'a Plugin,
+    ptr: *mut ::ffi::t_gui_completion,
+}
+
+impl CompletionPosition {
+    fn as_bytes(&self) -> &'static [u8] {
+        match *self {
+            CompletionPosition::Sort => ::ffi::WEECHAT_LIST_POS_SORT,
+            CompletionPosition::Beginning => ::ffi::WEECHAT_LIST_POS_BEGINNING,
+            CompletionPosition::End => ::ffi::WEECHAT_LIST_POS_END,
+        }
+    }
+}
+
+impl<'a> Completion<'a> {
+    pub(crate) fn new(plugin: &'a Plugin, ptr: *mut ::ffi::t_gui_completion) -> Self {
+        Self { plugin, ptr }
+    }
+
+    fn get_string(&self, property: &str) -> Result<String> {
+        let cproperty = CString::new(property)?;
+        let value = unsafe {
+            CStr::from_ptr(try_ptr!(
+                call_attr!(
+                    self.plugin.ptr,
+                    hook_completion_get_string,
+                    self.ptr,
+                    cproperty.as_ptr()
+                ),
+                "hook_completion_get_string"
+            ))
+        };
+        Ok(value.to_string_lossy().into_owned())
+    }
+
+    // The command being completed, without the leading slash
+    pub fn base_command(&self) -> Result<String> {
+        self.get_string("base_command")
+    }
+
+    // The word being completed
+    pub fn base_word(&self) -> Result<String> {
+        self.get_string("base_word")
+    }
+
+    // The command arguments, including the word being completed
+    pub fn args(&self) -> Result<String> {
+        self.get_string("args")
+    }
+
+    pub fn add(&self, word: &str, is_nick: bool, position: CompletionPosition) -> Result<()> {
+        let cword = CString::new(word)?;
+        unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hook_completion_list_add,
+                self.ptr,
+                cword.as_ptr(),
+                is_nick as i32,
+                position.as_bytes().as_ptr() as *const _
+            );
+        }
+        Ok(())
+    }
+
+    pub fn add_word(&self, word: &str) -> Result<()> {
+        self.add(word, false, CompletionPosition::Sort)
+    }
+}
@@ -0,0 +1,266 @@
+use super::callbacks::{self, malloc_callback, ConfigChangeHook};
+use super::{CallResult, Error, Plugin, Result};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+#[derive(Clone, Debug)]
+pub struct Config<'a> {
+    plugin: &'a Plugin,
+    pub(crate) ptr: *mut ::ffi::t_config_file,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfigSection<'a> {
+    config: Config<'a>,
+    ptr: *mut ::ffi::t_config_section,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfigOption<'a> {
+    plugin: &'a Plugin,
+    pub(crate) ptr: *mut ::ffi::t_config_option,
+}
+
+fn string_from_ptr(ptr: *const c_char, call: &'static str) -> Result<String> {
+    Ok(unsafe { CStr::from_ptr(try_ptr!(ptr, call)) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+impl<'a> Config<'a> {
+    pub(crate) fn new(plugin: &'a Plugin, ptr: *mut ::ffi::t_config_file) -> Self {
+        Self { plugin, ptr }
+    }
+
+    pub fn new_section(&self, name: &str) -> Result<ConfigSection<'a>> {
+        let cname = CString::new(name)?;
+
+        // We don't allow users to add or delete options, so none of the
+        // section callbacks are needed
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.plugin.ptr,
+                    config_new_section,
+                    self.ptr,
+                    cname.as_ptr(),
+                    0,
+                    0,
+                    None,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    None,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    None,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    None,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    None,
+                    ptr::null(),
+                    ptr::null_mut()
+                )
+            },
+            "config_new_section"
+        );
+        Ok(ConfigSection {
+            config: self.clone(),
+            ptr,
+        })
+    }
+
+    pub fn read(&self) -> CallResult {
+        match unsafe { call_attr!(self.plugin.ptr, config_read, self.ptr) } {
+            ::ffi::WEECHAT_CONFIG_READ_OK | ::ffi::WEECHAT_CONFIG_READ_FILE_NOT_FOUND => Ok(()),
+            rc => Err(Error::ReturnCode("config_read", rc)),
+        }
+    }
+
+    pub fn write(&self) -> CallResult {
+        match unsafe { call_attr!(self.plugin.ptr, config_write, self.ptr) } {
+            ::ffi::WEECHAT_CONFIG_WRITE_OK => Ok(()),
+            rc => Err(Error::ReturnCode("config_write", rc)),
+        }
+    }
+
+    pub fn reload(&self) -> CallResult {
+        match unsafe { call_attr!(self.plugin.ptr, config_reload, self.ptr) } {
+            ::ffi::WEECHAT_CONFIG_READ_OK | ::ffi::WEECHAT_CONFIG_READ_FILE_NOT_FOUND => Ok(()),
+            rc => Err(Error::ReturnCode("config_reload", rc)),
+        }
+    }
+
+    // Free the configuration file and all its options. The handle must not be
+    // used afterwards
+    pub fn free(self) {
+        unsafe { call_attr!(self.plugin.ptr, config_free, self.ptr) }
+    }
+}
+
+impl<'a> ConfigSection<'a> {
+    fn new_option(
+        &self,
+        name: &str,
+        option_type: &str,
+        description: &str,
+        (min, max): (i32, i32),
+        default: &str,
+        change: Option<ConfigChangeHook>,
+    ) -> Result<ConfigOption<'a>> {
+        let plugin = self.config.plugin;
+        let cname = CString::new(name)?;
+        let ctype = CString::new(option_type)?;
+        let cdescription = CString::new(description)?;
+        let cdefault = CString::new(default)?;
+
+        let change_callback: Option<
+            unsafe extern "C" fn(*const c_void, *mut c_void, *mut ::ffi::t_config_option),
+        > = match change {
+            Some(_) => Some(callbacks::config_option_change),
+            None => None,
+        };
+        let change_data = match change {
+            Some(callback) => malloc_callback(callback)? as *mut c_void,
+            None => ptr::null_mut(),
+        };
+
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    plugin.ptr,
+                    config_new_option,
+                    self.config.ptr,
+                    self.ptr,
+                    cname.as_ptr(),
+                    ctype.as_ptr(),
+                    cdescription.as_ptr(),
+                    ptr::null(),
+                    min,
+                    max,
+                    cdefault.as_ptr(),
+                    cdefault.as_ptr(),
+                    0,
+                    None,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    change_callback,
+                    plugin.ptr as *const c_void,
+                    change_data,
+                    None,
+                    ptr::null(),
+                    ptr::null_mut()
+                )
+            },
+            "config_new_option"
+        );
+        Ok(ConfigOption { plugin, ptr })
+    }
+
+    pub fn new_boolean(
+        &self,
+        name: &str,
+        description: &str,
+        default: bool,
+        change: Option<ConfigChangeHook>,
+    ) -> Result<ConfigOption<'a>> {
+        let default = if default { "on" } else { "off" };
+        self.new_option(name, "boolean", description, (0, 0), default, change)
+    }
+
+    pub fn new_integer(
+        &self,
+        name: &str,
+        description: &str,
+        min: i32,
+        max: i32,
+        default: i32,
+        change: Option<ConfigChangeHook>,
+    ) -> Result<ConfigOption<'a>> {
+        let default = default.to_string();
+        self.new_option(name, "integer", description, (min, max), &default, change)
+    }
+
+    pub fn new_string(
+        &self,
+        name: &str,
+        description: &str,
+        default: &str,
+        change: Option<ConfigChangeHook>,
+    ) -> Result<ConfigOption<'a>> {
+        self.new_option(name, "string", description, (0, 0), default, change)
+    }
+
+    pub fn new_color(
+        &self,
+        name: &str,
+        description: &str,
+        default: &str,
+        change: Option<ConfigChangeHook>,
+    ) -> Result<ConfigOption<'a>> {
+        self.new_option(name, "color", description, (0, 0), default, change)
+    }
+}
+
+impl<'a> ConfigOption<'a> {
+    pub(crate) fn new(plugin: &'a Plugin, ptr: *mut ::ffi::t_config_option) -> Self {
+        Self { plugin, ptr }
+    }
+
+    pub fn name(&self) -> Result<String> {
+        let cname = CString::new("name")?;
+        string_from_ptr(
+            unsafe {
+                call_attr!(
+                    self.plugin.ptr,
+                    config_option_get_string,
+                    self.ptr,
+                    cname.as_ptr()
+                )
+            },
+            "config_option_get_string",
+        )
+    }
+
+    pub fn boolean(&self) -> bool {
+        unsafe { call_attr!(self.plugin.ptr, config_boolean, self.ptr) != 0 }
+    }
+
+    pub fn integer(&self) -> i32 {
+        unsafe { call_attr!(self.plugin.ptr, config_integer, self.ptr) }
+    }
+
+    pub fn string(&self) -> Result<String> {
+        string_from_ptr(
+            unsafe { call_attr!(self.plugin.ptr, config_string, self.ptr) },
+            "config_string",
+        )
+    }
+
+    pub fn color(&self) -> Result<String> {
+        string_from_ptr(
+            unsafe { call_attr!(self.plugin.ptr, config_color, self.ptr) },
+            "config_color",
+        )
+    }
+
+    pub fn set(&self, value: &str, run_callback: bool) -> CallResult {
+        let cvalue = CString::new(value)?;
+        let rc = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                config_option_set,
+                self.ptr,
+                cvalue.as_ptr(),
+                run_callback as i32
+            )
+        };
+        match rc {
+            ::ffi::WEECHAT_CONFIG_OPTION_SET_OK_CHANGED
+            | ::ffi::WEECHAT_CONFIG_OPTION_SET_OK_SAME_VALUE => Ok(()),
+            rc => Err(Error::ReturnCode("config_option_set", rc)),
+        }
+    }
+}
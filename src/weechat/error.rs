@@ -0,0 +1,65 @@
+use std::ffi::NulError;
+use std::fmt;
+use std::str::Utf8Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    // A string passed to Weechat contained a nul byte
+    Nul(NulError),
+    // A string returned by Weechat was not valid UTF-8
+    Utf8(Utf8Error),
+    // A Weechat API function returned a null pointer
+    NullPointer(&'static str),
+    // A pointer did not point to a live object of the given hdata type
+    InvalidPointer(String),
+    // A Weechat API function returned an unexpected return code
+    ReturnCode(&'static str, i32),
+    // A hdata variable or hashtable value did not have the expected type
+    TypeMismatch { name: String, expected: String },
+    // A named object like a hdata variable or configuration file was missing
+    NotFound(String),
+    // A value that Weechat does not allow us to change
+    ReadOnly(String),
+    Dbus { call: String, message: String },
+    // Rust code called from Weechat panicked
+    Panic(String),
+    Other(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Nul(ref e) => write!(f, "string contains a nul byte: {}", e),
+            Error::Utf8(ref e) => write!(f, "string is not valid UTF-8: {}", e),
+            Error::NullPointer(call) => write!(f, "{} returned a null pointer", call),
+            Error::InvalidPointer(ref hdata) => write!(f, "pointer is not a valid {}", hdata),
+            Error::ReturnCode(call, code) => write!(f, "{} returned {}", call, code),
+            Error::TypeMismatch {
+                ref name,
+                ref expected,
+            } => write!(f, "{} is not of type {}", name, expected),
+            Error::NotFound(ref name) => write!(f, "{} not found", name),
+            Error::ReadOnly(ref name) => write!(f, "{} is read-only", name),
+            Error::Dbus {
+                ref call,
+                ref message,
+            } => write!(f, "DBus call {} failed: {}", call, message),
+            Error::Panic(ref message) => write!(f, "panicked: {}", message),
+            Error::Other(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<NulError> for Error {
+    fn from(err: NulError) -> Self {
+        Error::Nul(err)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(err: Utf8Error) -> Self {
+        Error::Utf8(err)
+    }
+}
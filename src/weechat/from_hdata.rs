@@ -0,0 +1,152 @@
+use super::{Error, Hashtable, Hdata, HdataValue, Plugin, Result};
+use std::ptr;
+
+// Types that can be read from a hdata variable. VAR_TYPES lists the hdata
+// types the variable may have, which is checked when validating a mapping
+pub trait HdataField<'a>: Sized {
+    const VAR_TYPES: &'static [i32];
+    const TYPE_NAME: &'static str;
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self>;
+}
+
+// Structs that map to a Weechat hdata type. This is usually implemented
+// using #[derive(FromHdata)] from weedesktop_macro
+pub trait FromHdata<'a>: Sized {
+    const HDATA_NAME: &'static str;
+
+    // Check that all mapped variables exist and have the expected types. This
+    // only needs the hdata definition so it can be done when loading
+    fn validate(plugin: &Plugin) -> Result<()>;
+
+    fn from_hdata(hdata: &Hdata<'a>) -> Result<Self>;
+}
+
+// Check that a variable exists and has a type that T can be read from.
+// Reading the type only uses the hdata definition, not the data pointer
+fn check_var<'a, T: HdataField<'a>>(hdata: &Hdata, name: &str, label: &str) -> Result<()> {
+    let var_type = hdata.get_type(name)?;
+    if var_type < 0 {
+        return Err(Error::NotFound(format!("hdata variable {}", label)));
+    }
+    if !T::VAR_TYPES.contains(&var_type) {
+        return Err(Error::TypeMismatch {
+            name: format!("hdata variable {}", label),
+            expected: T::TYPE_NAME.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+// Used by the generated FromHdata::validate implementations
+pub fn validate_var<'a, T: HdataField<'a>>(
+    plugin: &Plugin,
+    hdata_name: &str,
+    name: &str,
+) -> Result<()> {
+    let hdata = plugin.hdata_from_ptr(hdata_name, ptr::null_mut())?;
+    check_var::<T>(&hdata, name, &format!("{}.{}", hdata_name, name))
+}
+
+impl<'a> HdataField<'a> for i8 {
+    const VAR_TYPES: &'static [i32] = &[::ffi::WEECHAT_HDATA_CHAR];
+    const TYPE_NAME: &'static str = "char";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        hdata.get_i8(name)
+    }
+}
+
+impl<'a> HdataField<'a> for i32 {
+    const VAR_TYPES: &'static [i32] = &[::ffi::WEECHAT_HDATA_INTEGER];
+    const TYPE_NAME: &'static str = "integer";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        hdata.get_i32(name)
+    }
+}
+
+impl<'a> HdataField<'a> for i64 {
+    const VAR_TYPES: &'static [i32] = &[::ffi::WEECHAT_HDATA_LONG, ::ffi::WEECHAT_HDATA_TIME];
+    const TYPE_NAME: &'static str = "long";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        match hdata.get(name) {
+            HdataValue::I64(v) => Ok(v),
+            HdataValue::Time(v) => Ok(v as i64),
+            _ => hdata.get_i64(name),
+        }
+    }
+}
+
+// Weechat uses both chars and integers for flags
+impl<'a> HdataField<'a> for bool {
+    const VAR_TYPES: &'static [i32] = &[::ffi::WEECHAT_HDATA_CHAR, ::ffi::WEECHAT_HDATA_INTEGER];
+    const TYPE_NAME: &'static str = "char or integer";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        match hdata.get(name) {
+            HdataValue::I8(v) => Ok(v != 0),
+            _ => Ok(hdata.get_i32(name)? != 0),
+        }
+    }
+}
+
+impl<'a> HdataField<'a> for &'a str {
+    const VAR_TYPES: &'static [i32] = &[
+        ::ffi::WEECHAT_HDATA_STRING,
+        ::ffi::WEECHAT_HDATA_SHARED_STRING,
+    ];
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        hdata.get_str(name)
+    }
+}
+
+impl<'a> HdataField<'a> for String {
+    const VAR_TYPES: &'static [i32] = &[
+        ::ffi::WEECHAT_HDATA_STRING,
+        ::ffi::WEECHAT_HDATA_SHARED_STRING,
+    ];
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        Ok(hdata.get_str(name)?.to_owned())
+    }
+}
+
+impl<'a> HdataField<'a> for Hdata<'a> {
+    const VAR_TYPES: &'static [i32] = &[::ffi::WEECHAT_HDATA_POINTER];
+    const TYPE_NAME: &'static str = "pointer";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        hdata.get_hdata(name)
+    }
+}
+
+impl<'a> HdataField<'a> for Hashtable<'a> {
+    const VAR_TYPES: &'static [i32] = &[::ffi::WEECHAT_HDATA_HASHTABLE];
+    const TYPE_NAME: &'static str = "hashtable";
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        hdata.get_hashtable(name)
+    }
+}
+
+// Optional fields are None when the value is a null pointer, but a missing
+// variable or a type mismatch is still an error
+impl<'a, T: HdataField<'a>> HdataField<'a> for Option<T> {
+    const VAR_TYPES: &'static [i32] = T::VAR_TYPES;
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
+    fn from_hdata_var(hdata: &Hdata<'a>, name: &str) -> Result<Self> {
+        match hdata.get(name) {
+            HdataValue::None => {
+                check_var::<T>(hdata, name, name)?;
+                Ok(None)
+            },
+            _ => Ok(Some(T::from_hdata_var(hdata, name)?)),
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use super::{catch_panic, Error, Plugin, Result};
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+// Buffer is only listed so hashtables created by Weechat can be wrapped. Their
+// values can't be read or written since the plugin API does not expose sizes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashtableType {
+    Integer,
+    Str,
+    Pointer,
+    Buffer,
+    Time,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HashtableValue {
+    Integer(i32),
+    Str(String),
+    Pointer(*mut c_void),
+    Time(libc::time_t),
+}
+
+#[derive(Debug)]
+pub struct Hashtable<'a> {
+    pub(crate) plugin: &'a Plugin,
+    pub(crate) ptr: *mut ::ffi::t_hashtable,
+    key_type: HashtableType,
+    value_type: HashtableType,
+    owned: bool,
+}
+
+impl HashtableType {
+    fn as_bytes(&self) -> &'static [u8] {
+        match *self {
+            HashtableType::Integer => ::ffi::WEECHAT_HASHTABLE_INTEGER,
+            HashtableType::Str => ::ffi::WEECHAT_HASHTABLE_STRING,
+            HashtableType::Pointer => ::ffi::WEECHAT_HASHTABLE_POINTER,
+            HashtableType::Buffer => ::ffi::WEECHAT_HASHTABLE_BUFFER,
+            HashtableType::Time => ::ffi::WEECHAT_HASHTABLE_TIME,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HashtableType::Integer => "integer",
+            HashtableType::Str => "string",
+            HashtableType::Pointer => "pointer",
+            HashtableType::Buffer => "buffer",
+            HashtableType::Time => "time",
+        }
+    }
+
+    fn from_cstr(name: &CStr) -> Result<Self> {
+        for t in &[
+            HashtableType::Integer,
+            HashtableType::Str,
+            HashtableType::Pointer,
+            HashtableType::Buffer,
+            HashtableType::Time,
+        ] {
+            if name.to_bytes_with_nul() == t.as_bytes() {
+                return Ok(*t);
+            }
+        }
+        Err(Error::NotFound(format!(
+            "hashtable type {}",
+            name.to_string_lossy()
+        )))
+    }
+}
+
+impl HashtableValue {
+    pub fn get_type(&self) -> HashtableType {
+        match *self {
+            HashtableValue::Integer(_) => HashtableType::Integer,
+            HashtableValue::Str(_) => HashtableType::Str,
+            HashtableValue::Pointer(_) => HashtableType::Pointer,
+            HashtableValue::Time(_) => HashtableType::Time,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            HashtableValue::Str(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // Decode a key or value pointer as handed out by Weechat. Integers, times
+    // and strings are stored by reference while pointers are stored as is.
+    // Buffers are skipped since their size is unknown
+    unsafe fn from_raw(value_type: HashtableType, ptr: *const c_void) -> Option<Self> {
+        match value_type {
+            HashtableType::Pointer => return Some(HashtableValue::Pointer(ptr as *mut c_void)),
+            HashtableType::Buffer => return None,
+            _ => {},
+        }
+
+        if ptr.is_null() {
+            return None;
+        }
+
+        Some(match value_type {
+            HashtableType::Integer => HashtableValue::Integer(*(ptr as *const c_int)),
+            HashtableType::Str => HashtableValue::Str(
+                CStr::from_ptr(ptr as *const c_char)
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            HashtableType::Time => HashtableValue::Time(*(ptr as *const libc::time_t)),
+            _ => unreachable!(),
+        })
+    }
+
+    // Call the given function with a pointer that Weechat understands as this
+    // value. The pointer is only valid for the duration of the call
+    fn with_raw<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(*const c_void) -> T,
+    {
+        Ok(match *self {
+            HashtableValue::Integer(ref i) => f(i as *const i32 as *const c_void),
+            HashtableValue::Str(ref s) => {
+                let cstr = CString::new(s.as_str())?;
+                f(cstr.as_ptr() as *const c_void)
+            },
+            HashtableValue::Pointer(p) => f(p as *const c_void),
+            HashtableValue::Time(ref t) => f(t as *const libc::time_t as *const c_void),
+        })
+    }
+}
+
+impl From<i32> for HashtableValue {
+    fn from(value: i32) -> Self {
+        HashtableValue::Integer(value)
+    }
+}
+
+impl<'a> From<&'a str> for HashtableValue {
+    fn from(value: &'a str) -> Self {
+        HashtableValue::Str(value.to_owned())
+    }
+}
+
+impl From<String> for HashtableValue {
+    fn from(value: String) -> Self {
+        HashtableValue::Str(value)
+    }
+}
+
+extern "C" fn hashtable_map_collect(
+    data: *mut c_void,
+    _hashtable: *mut ::ffi::t_hashtable,
+    key: *const c_void,
+    value: *const c_void,
+) {
+    let state = unsafe { &mut *(data as *mut MapState) };
+    catch_panic(state.plugin, (), || {
+        let key = unsafe { HashtableValue::from_raw(state.key_type, key) };
+        let value = unsafe { HashtableValue::from_raw(state.value_type, value) };
+        if let (Some(k), Some(v)) = (key, value) {
+            state.items.push((k, v));
+        }
+    })
+}
+
+fn get_property<'a>(
+    plugin: &'a Plugin,
+    ptr: *mut ::ffi::t_hashtable,
+    property: &str,
+) -> Result<&'a CStr> {
+    let cproperty = CString::new(property)?;
+    Ok(unsafe {
+        CStr::from_ptr(try_ptr!(
+            call_attr!(plugin.ptr, hashtable_get_string, ptr, cproperty.as_ptr()),
+            "hashtable_get_string"
+        ))
+    })
+}
+
+struct MapState<'a> {
+    plugin: &'a Plugin,
+    key_type: HashtableType,
+    value_type: HashtableType,
+    items: Vec<(HashtableValue, HashtableValue)>,
+}
+
+impl<'a> Hashtable<'a> {
+    pub(crate) fn new(
+        plugin: &'a Plugin,
+        key_type: HashtableType,
+        value_type: HashtableType,
+    ) -> Result<Self> {
+        if key_type == HashtableType::Buffer || value_type == HashtableType::Buffer {
+            return Err(Error::Other(
+                "hashtables of buffers are not supported".to_owned(),
+            ));
+        }
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    plugin.ptr,
+                    hashtable_new,
+                    32,
+                    key_type.as_bytes().as_ptr() as *const c_char,
+                    value_type.as_bytes().as_ptr() as *const c_char,
+                    None,
+                    None
+                )
+            },
+            "hashtable_new"
+        );
+        Ok(Self {
+            plugin,
+            ptr,
+            key_type,
+            value_type,
+            owned: true,
+        })
+    }
+
+    // Wrap a hashtable owned by Weechat. It will not be freed when dropped
+    pub(crate) fn from_ptr(plugin: &'a Plugin, ptr: *mut ::ffi::t_hashtable) -> Result<Self> {
+        let ptr = try_ptr!(ptr, "hdata_hashtable");
+        let key_type = HashtableType::from_cstr(get_property(plugin, ptr, "type_keys")?)?;
+        let value_type = HashtableType::from_cstr(get_property(plugin, ptr, "type_values")?)?;
+        Ok(Self {
+            plugin,
+            ptr,
+            key_type,
+            value_type,
+            owned: false,
+        })
+    }
+
+    // Take ownership of a hashtable that we are responsible for freeing
+    pub(crate) fn from_owned_ptr(plugin: &'a Plugin, ptr: *mut ::ffi::t_hashtable) -> Result<Self> {
+        let mut hashtable = Self::from_ptr(plugin, ptr)?;
+        hashtable.owned = true;
+        Ok(hashtable)
+    }
+
+    // Give up ownership, for when Weechat takes over freeing the hashtable
+    pub(crate) fn into_raw(mut self) -> *mut ::ffi::t_hashtable {
+        self.owned = false;
+        self.ptr
+    }
+
+    fn check_type(&self, value: &HashtableValue, expected: HashtableType) -> Result<()> {
+        if value.get_type() != expected {
+            return Err(Error::TypeMismatch {
+                name: format!("{:?}", value),
+                expected: expected.name().to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn key_type(&self) -> HashtableType {
+        self.key_type
+    }
+
+    pub fn value_type(&self) -> HashtableType {
+        self.value_type
+    }
+
+    pub fn len(&self) -> usize {
+        let count = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hashtable_get_integer,
+                self.ptr,
+                b"items_count\0".as_ptr() as *const _
+            )
+        };
+        count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key<K: Into<HashtableValue>>(&self, key: K) -> Result<bool> {
+        let key = key.into();
+        self.check_type(&key, self.key_type)?;
+        let has_key = key.with_raw(|key_ptr| unsafe {
+            call_attr!(self.plugin.ptr, hashtable_has_key, self.ptr, key_ptr)
+        })?;
+        Ok(has_key != 0)
+    }
+
+    pub fn get<K: Into<HashtableValue>>(&self, key: K) -> Result<Option<HashtableValue>> {
+        let key = key.into();
+        if !self.contains_key(key.clone())? {
+            return Ok(None);
+        }
+
+        let value_ptr = key.with_raw(|key_ptr| unsafe {
+            call_attr!(self.plugin.ptr, hashtable_get, self.ptr, key_ptr)
+        })?;
+        Ok(unsafe { HashtableValue::from_raw(self.value_type, value_ptr) })
+    }
+
+    pub fn get_str<K: Into<HashtableValue>>(&self, key: K) -> Result<Option<String>> {
+        match self.get(key)? {
+            Some(HashtableValue::Str(s)) => Ok(Some(s)),
+            Some(v) => Err(Error::TypeMismatch {
+                name: format!("{:?}", v),
+                expected: HashtableType::Str.name().to_owned(),
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set<K, V>(&self, key: K, value: V) -> Result<()>
+    where
+        K: Into<HashtableValue>,
+        V: Into<HashtableValue>,
+    {
+        let key = key.into();
+        let value = value.into();
+        self.check_type(&key, self.key_type)?;
+        self.check_type(&value, self.value_type)?;
+
+        let item_ptr = key.with_raw(|key_ptr| {
+            value.with_raw(|value_ptr| unsafe {
+                call_attr!(self.plugin.ptr, hashtable_set, self.ptr, key_ptr, value_ptr)
+            })
+        })??;
+        try_ptr!(item_ptr, "hashtable_set");
+        Ok(())
+    }
+
+    pub fn remove<K: Into<HashtableValue>>(&self, key: K) -> Result<()> {
+        let key = key.into();
+        self.check_type(&key, self.key_type)?;
+        key.with_raw(|key_ptr| unsafe {
+            call_attr!(self.plugin.ptr, hashtable_remove, self.ptr, key_ptr)
+        })
+    }
+
+    pub fn clear(&self) {
+        unsafe { call_attr!(self.plugin.ptr, hashtable_remove_all, self.ptr) }
+    }
+
+    pub fn iter(&self) -> std::vec::IntoIter<(HashtableValue, HashtableValue)> {
+        // Weechat only offers a callback based API for iteration so we collect
+        // all items up front
+        let mut state = MapState {
+            plugin: self.plugin,
+            key_type: self.key_type,
+            value_type: self.value_type,
+            items: Vec::with_capacity(self.len()),
+        };
+        unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hashtable_map,
+                self.ptr,
+                Some(hashtable_map_collect),
+                &mut state as *mut MapState as *mut c_void
+            );
+        }
+        state.items.into_iter()
+    }
+
+    pub fn to_map(&self) -> HashMap<HashtableValue, HashtableValue> {
+        self.iter().collect()
+    }
+
+    pub fn to_string_map(&self) -> Result<HashMap<String, String>> {
+        if self.key_type != HashtableType::Str || self.value_type != HashtableType::Str {
+            return Err(Error::TypeMismatch {
+                name: format!(
+                    "hashtable of {} to {}",
+                    self.key_type.name(),
+                    self.value_type.name()
+                ),
+                expected: "hashtable of string to string".to_owned(),
+            });
+        }
+        Ok(self
+            .iter()
+            .filter_map(|(k, v)| match (k, v) {
+                (HashtableValue::Str(k), HashtableValue::Str(v)) => Some((k, v)),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+impl<'a> Clone for Hashtable<'a> {
+    fn clone(&self) -> Self {
+        // Borrowed hashtables can share the pointer, but owned hashtables must
+        // be duplicated to avoid freeing the same pointer twice
+        let ptr = if self.owned {
+            unsafe { call_attr!(self.plugin.ptr, hashtable_dup, self.ptr) }
+        } else {
+            self.ptr
+        };
+        Self {
+            plugin: self.plugin,
+            ptr,
+            key_type: self.key_type,
+            value_type: self.value_type,
+            owned: self.owned && !ptr.is_null(),
+        }
+    }
+}
+
+impl<'a> Drop for Hashtable<'a> {
+    fn drop(&mut self) {
+        if self.owned && !self.ptr.is_null() {
+            unsafe { call_attr!(self.plugin.ptr, hashtable_free, self.ptr) }
+            self.ptr = ptr::null_mut();
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Hashtable<'a>> for HashMap<HashtableValue, HashtableValue> {
+    fn from(hashtable: &'b Hashtable<'a>) -> Self {
+        hashtable.to_map()
+    }
+}
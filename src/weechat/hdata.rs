@@ -1,4 +1,4 @@
-use super::{Plugin, Result};
+use super::{CallResult, Error, FromHdata, Hashtable, HashtableType, Plugin, Result};
 use std::ffi::{c_void, CStr, CString};
 use std::ptr;
 
@@ -13,6 +13,8 @@ pub struct Hdata<'a> {
 pub struct HdataIterator<'a> {
     next_hdata: Option<Hdata<'a>>,
     next_key: &'a CStr,
+    // Name of the list every element is checked against before it is read
+    check_list: Option<CString>,
 }
 
 #[derive(Clone, Debug)]
@@ -25,10 +27,23 @@ pub enum HdataValue<'a> {
     Ptr(*mut c_void),
     Hdata(Hdata<'a>),
     Time(libc::time_t),
-    Hashtable(*mut ::ffi::t_hashtable), // TODO: Implement Hashtable as type
+    Hashtable(Hashtable<'a>),
     None,
 }
 
+fn value_type_name(var_type: i32) -> &'static str {
+    match var_type {
+        ::ffi::WEECHAT_HDATA_CHAR => "char",
+        ::ffi::WEECHAT_HDATA_INTEGER => "integer",
+        ::ffi::WEECHAT_HDATA_LONG => "long",
+        ::ffi::WEECHAT_HDATA_STRING | ::ffi::WEECHAT_HDATA_SHARED_STRING => "string",
+        ::ffi::WEECHAT_HDATA_POINTER => "pointer",
+        ::ffi::WEECHAT_HDATA_TIME => "time",
+        ::ffi::WEECHAT_HDATA_HASHTABLE => "hashtable",
+        _ => "other",
+    }
+}
+
 impl<'a> Hdata<'a> {
     pub(crate) fn new(
         plugin: &'a Plugin,
@@ -53,7 +68,82 @@ impl<'a> Hdata<'a> {
         }
     }
 
+    // Get the raw Weechat type of a variable, or -1 if it does not exist
+    pub fn get_type(&self, name: &str) -> Result<i32> {
+        Ok(self.get_type_from_cstr(&CString::new(name)?))
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.data_ptr
+    }
+
+    // Get a readable name for the type of a variable, e.g. "integer"
+    pub fn get_type_name(&self, name: &str) -> Result<&'static str> {
+        match self.get_type(name)? {
+            t if t < 0 => Err(Error::NotFound(format!("hdata variable {}", name))),
+            t => Ok(value_type_name(t)),
+        }
+    }
+
+    // Names of all variables of this hdata type, in declaration order
+    pub fn var_keys(&self) -> Result<Vec<&'a str>> {
+        let ckey = CString::new("var_keys")?;
+        let keys = unsafe {
+            CStr::from_ptr(try_ptr!(
+                call_attr!(
+                    self.plugin.ptr,
+                    hdata_get_string,
+                    self.hdata_ptr,
+                    ckey.as_ptr()
+                ),
+                "hdata_get_string"
+            ))
+        };
+        Ok(keys
+            .to_str()?
+            .split(',')
+            .filter(|k| !k.is_empty())
+            .collect())
+    }
+
+    // Names of the variables linking to the previous and next element of a
+    // list, if this hdata type has any
+    pub fn var_prev(&self) -> Result<Option<&'a str>> {
+        self.get_property("var_prev")
+    }
+
+    pub fn var_next(&self) -> Result<Option<&'a str>> {
+        self.get_property("var_next")
+    }
+
+    fn get_property(&self, property: &str) -> Result<Option<&'a str>> {
+        let cproperty = CString::new(property)?;
+        let value = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_get_string,
+                self.hdata_ptr,
+                cproperty.as_ptr()
+            )
+        };
+        if value.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(unsafe { CStr::from_ptr(value) }.to_str()?))
+    }
+
+    // Read the whole hdata into a struct that implements FromHdata
+    pub fn parse<T: FromHdata<'a>>(&self) -> Result<T> {
+        T::from_hdata(self)
+    }
+
     fn get_from_cstr(&self, cname: &CStr) -> HdataValue<'a> {
+        self.get_value(cname, cname)
+    }
+
+    // Read a value where cname is the name of the variable and cvalue_name
+    // is what is passed when reading, which may include an array index
+    fn get_value(&self, cname: &CStr, cvalue_name: &CStr) -> HdataValue<'a> {
         match self.get_type_from_cstr(cname) {
             ::ffi::WEECHAT_HDATA_OTHER => {
                 let ptr = unsafe {
@@ -62,7 +152,7 @@ impl<'a> Hdata<'a> {
                         hdata_get_var,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
                 HdataValue::Other(ptr)
@@ -74,7 +164,7 @@ impl<'a> Hdata<'a> {
                         hdata_char,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
                 HdataValue::I8(chr)
@@ -86,7 +176,7 @@ impl<'a> Hdata<'a> {
                         hdata_integer,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
                 HdataValue::I32(int)
@@ -98,7 +188,7 @@ impl<'a> Hdata<'a> {
                         hdata_long,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
                 HdataValue::I64(long)
@@ -110,9 +200,12 @@ impl<'a> Hdata<'a> {
                         hdata_string,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
+                if char_ptr.is_null() {
+                    return HdataValue::None;
+                }
                 HdataValue::Str(unsafe { CStr::from_ptr(char_ptr) })
             },
             ::ffi::WEECHAT_HDATA_POINTER => {
@@ -122,7 +215,7 @@ impl<'a> Hdata<'a> {
                         hdata_pointer,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
                 if ptr == ptr::null_mut() {
@@ -139,11 +232,11 @@ impl<'a> Hdata<'a> {
                 if hdata_name_ptr == ptr::null_mut() {
                     return HdataValue::Ptr(ptr);
                 }
-                let hdata_cname = unsafe { CStr::from_ptr(hdata_name_ptr) };
-                match self
-                    .plugin
-                    .hdata_from_ptr(hdata_cname.to_str().unwrap(), ptr)
-                {
+                let hdata_name = match unsafe { CStr::from_ptr(hdata_name_ptr) }.to_str() {
+                    Ok(name) => name,
+                    Err(_) => return HdataValue::Ptr(ptr),
+                };
+                match self.plugin.hdata_from_ptr(hdata_name, ptr) {
                     Ok(h) => HdataValue::Hdata(h),
                     Err(_) => HdataValue::None,
                 }
@@ -155,7 +248,7 @@ impl<'a> Hdata<'a> {
                         hdata_time,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
                 HdataValue::Time(time)
@@ -167,10 +260,13 @@ impl<'a> Hdata<'a> {
                         hdata_hashtable,
                         self.hdata_ptr,
                         self.data_ptr,
-                        cname.as_ptr()
+                        cvalue_name.as_ptr()
                     )
                 };
-                HdataValue::Hashtable(hashtable_ptr)
+                match Hashtable::from_ptr(self.plugin, hashtable_ptr) {
+                    Ok(h) => HdataValue::Hashtable(h),
+                    Err(_) => HdataValue::None,
+                }
             },
             _ => HdataValue::None,
         }
@@ -184,59 +280,395 @@ impl<'a> Hdata<'a> {
         self.get_from_cstr(cname.as_c_str())
     }
 
+    pub fn get_array_size(&self, name: &str) -> Result<usize> {
+        let cname = CString::new(name)?;
+        let size = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_get_var_array_size,
+                self.hdata_ptr,
+                self.data_ptr,
+                cname.as_ptr()
+            )
+        };
+        if size < 0 {
+            return Err(Error::TypeMismatch {
+                name: format!("hdata variable {}", name),
+                expected: "array".to_owned(),
+            });
+        }
+        Ok(size as usize)
+    }
+
+    pub fn get_array(&self, name: &str) -> Result<Vec<HdataValue<'a>>> {
+        let cname = CString::new(name)?;
+        let size = self.get_array_size(name)?;
+
+        // Array elements are read using the "index|name" syntax
+        let mut values = Vec::with_capacity(size);
+        for i in 0..size {
+            let cvalue_name = CString::new(format!("{}|{}", i, name))?;
+            values.push(self.get_value(&cname, &cvalue_name));
+        }
+        Ok(values)
+    }
+
+    fn type_error(&self, name: &str, value: HdataValue<'a>, expected: &str) -> Error {
+        let var = format!("hdata variable {}", name);
+        let exists = match CString::new(name) {
+            Ok(cname) => self.get_type_from_cstr(&cname) >= 0,
+            Err(e) => return Error::Nul(e),
+        };
+        match value {
+            _ if !exists => Error::NotFound(var),
+            HdataValue::None => Error::NotFound(format!("value of {}", var)),
+            _ => Error::TypeMismatch {
+                name: var,
+                expected: expected.to_owned(),
+            },
+        }
+    }
+
     pub fn get_i8(&self, name: &str) -> Result<i8> {
         match self.get(name) {
             HdataValue::I8(v) => Ok(v),
-            _ => Err(()),
+            v => Err(self.type_error(name, v, "char")),
         }
     }
 
     pub fn get_i32(&self, name: &str) -> Result<i32> {
         match self.get(name) {
             HdataValue::I32(v) => Ok(v),
-            _ => Err(()),
+            v => Err(self.type_error(name, v, "integer")),
         }
     }
 
     pub fn get_i64(&self, name: &str) -> Result<i64> {
         match self.get(name) {
             HdataValue::I64(v) => Ok(v),
-            _ => Err(()),
+            v => Err(self.type_error(name, v, "long")),
+        }
+    }
+
+    pub fn get_time(&self, name: &str) -> Result<libc::time_t> {
+        match self.get(name) {
+            HdataValue::Time(v) => Ok(v),
+            v => Err(self.type_error(name, v, "time")),
         }
     }
 
     pub fn get_cstr(&self, name: &str) -> Result<&'a CStr> {
         match self.get(name) {
             HdataValue::Str(v) => Ok(&v),
-            _ => Err(()),
+            v => Err(self.type_error(name, v, "string")),
         }
     }
 
     pub fn get_str(&self, name: &str) -> Result<&'a str> {
-        self.get_cstr(name)?.to_str().or(Err(()))
+        Ok(self.get_cstr(name)?.to_str()?)
     }
 
     pub fn get_hdata(&self, name: &str) -> Result<Hdata<'a>> {
         match self.get(name) {
             HdataValue::Hdata(v) => Ok(v),
-            _ => Err(()),
+            v => Err(self.type_error(name, v, "hdata pointer")),
         }
     }
 
-    pub fn try_iter(&self) -> Result<HdataIterator<'a>> {
-        let cnext = CString::new("var_next").or(Err(()))?;
-        let var_next = unsafe {
-            CStr::from_ptr(try_ptr!(call_attr!(
+    pub fn get_hashtable(&self, name: &str) -> Result<Hashtable<'a>> {
+        match self.get(name) {
+            HdataValue::Hashtable(v) => Ok(v),
+            v => Err(self.type_error(name, v, "hashtable")),
+        }
+    }
+
+    pub fn get_i8_array(&self, name: &str) -> Result<Vec<i8>> {
+        self.get_array(name)?
+            .into_iter()
+            .map(|v| match v {
+                HdataValue::I8(v) => Ok(v),
+                v => Err(self.type_error(name, v, "char array")),
+            })
+            .collect()
+    }
+
+    pub fn get_i32_array(&self, name: &str) -> Result<Vec<i32>> {
+        self.get_array(name)?
+            .into_iter()
+            .map(|v| match v {
+                HdataValue::I32(v) => Ok(v),
+                v => Err(self.type_error(name, v, "integer array")),
+            })
+            .collect()
+    }
+
+    pub fn get_i64_array(&self, name: &str) -> Result<Vec<i64>> {
+        self.get_array(name)?
+            .into_iter()
+            .map(|v| match v {
+                HdataValue::I64(v) => Ok(v),
+                v => Err(self.type_error(name, v, "long array")),
+            })
+            .collect()
+    }
+
+    pub fn get_time_array(&self, name: &str) -> Result<Vec<libc::time_t>> {
+        self.get_array(name)?
+            .into_iter()
+            .map(|v| match v {
+                HdataValue::Time(v) => Ok(v),
+                v => Err(self.type_error(name, v, "time array")),
+            })
+            .collect()
+    }
+
+    pub fn get_str_array(&self, name: &str) -> Result<Vec<&'a str>> {
+        self.get_array(name)?
+            .into_iter()
+            .map(|v| match v {
+                HdataValue::Str(v) => v.to_str().map_err(Error::from),
+                v => Err(self.type_error(name, v, "string array")),
+            })
+            .collect()
+    }
+
+    pub fn get_hdata_array(&self, name: &str) -> Result<Vec<Hdata<'a>>> {
+        self.get_array(name)?
+            .into_iter()
+            .map(|v| match v {
+                HdataValue::Hdata(v) => Ok(v),
+                v => Err(self.type_error(name, v, "hdata pointer array")),
+            })
+            .collect()
+    }
+
+    // Check whether Weechat allows updating the given variable through
+    // hdata_update
+    pub fn can_update(&self, name: &str) -> Result<bool> {
+        let hashtable = self
+            .plugin
+            .hashtable_new(HashtableType::Str, HashtableType::Str)?;
+        hashtable.set("__update_allowed", name)?;
+        let allowed = unsafe {
+            call_attr!(
                 self.plugin.ptr,
-                hdata_get_string,
+                hdata_update,
+                self.hdata_ptr,
+                self.data_ptr,
+                hashtable.ptr
+            )
+        };
+        Ok(allowed != 0)
+    }
+
+    // The value is passed as a C string since Weechat parses it whatever the
+    // type, and chars must be able to hold bytes that are not valid UTF-8
+    fn update_var(&self, name: &str, expected: &[i32], value: &CStr) -> CallResult {
+        let cname = CString::new(name)?;
+        let var_type = self.get_type_from_cstr(&cname);
+        if var_type < 0 {
+            return Err(Error::NotFound(format!("hdata variable {}", name)));
+        }
+        if !expected.contains(&var_type) {
+            return Err(Error::TypeMismatch {
+                name: format!("hdata variable {}", name),
+                expected: value_type_name(expected[0]).to_owned(),
+            });
+        }
+        if !self.can_update(name)? {
+            return Err(Error::ReadOnly(format!("hdata variable {}", name)));
+        }
+
+        let hashtable = self
+            .plugin
+            .hashtable_new(HashtableType::Str, HashtableType::Str)?;
+        try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.plugin.ptr,
+                    hashtable_set,
+                    hashtable.ptr,
+                    cname.as_ptr() as *const c_void,
+                    value.as_ptr() as *const c_void
+                )
+            },
+            "hashtable_set"
+        );
+        let updated = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_update,
                 self.hdata_ptr,
-                cnext.as_ptr()
-            )))
+                self.data_ptr,
+                hashtable.ptr
+            )
+        };
+        if updated < 1 {
+            return Err(Error::ReturnCode("hdata_update", updated));
+        }
+        Ok(())
+    }
+
+    pub fn update_i8(&self, name: &str, value: i8) -> CallResult {
+        // Weechat stores the first byte of the string, so 0 is an empty string
+        let value = if value == 0 {
+            CString::default()
+        } else {
+            CString::new(vec![value as u8])?
+        };
+        self.update_var(name, &[::ffi::WEECHAT_HDATA_CHAR], &value)
+    }
+
+    pub fn update_i32(&self, name: &str, value: i32) -> CallResult {
+        let value = CString::new(value.to_string())?;
+        self.update_var(name, &[::ffi::WEECHAT_HDATA_INTEGER], &value)
+    }
+
+    pub fn update_i64(&self, name: &str, value: i64) -> CallResult {
+        let value = CString::new(value.to_string())?;
+        self.update_var(name, &[::ffi::WEECHAT_HDATA_LONG], &value)
+    }
+
+    pub fn update_str(&self, name: &str, value: &str) -> CallResult {
+        let value = CString::new(value)?;
+        self.update_var(
+            name,
+            &[
+                ::ffi::WEECHAT_HDATA_STRING,
+                ::ffi::WEECHAT_HDATA_SHARED_STRING,
+            ],
+            &value,
+        )
+    }
+
+    pub fn update_ptr(&self, name: &str, value: *mut c_void) -> CallResult {
+        let value = CString::new(format!("0x{:x}", value as usize))?;
+        self.update_var(name, &[::ffi::WEECHAT_HDATA_POINTER], &value)
+    }
+
+    pub fn update_time(&self, name: &str, value: libc::time_t) -> CallResult {
+        let value = CString::new(value.to_string())?;
+        self.update_var(name, &[::ffi::WEECHAT_HDATA_TIME], &value)
+    }
+
+    // Move count elements forward or backward in the list this element belongs
+    // to. Returns None when moving past either end of the list
+    pub fn move_by(&self, count: i32) -> Option<Hdata<'a>> {
+        let ptr = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_move,
+                self.hdata_ptr,
+                self.data_ptr,
+                count
+            )
+        };
+        if ptr.is_null() {
+            return None;
+        }
+        Some(Hdata::new(self.plugin, self.hdata_ptr, ptr))
+    }
+
+    // Find the first element, starting with this one, for which the given
+    // Weechat expression is true. The current element is available in the
+    // expression using the hdata name, e.g. "${irc_server.name} == libera".
+    // A negative step searches backward
+    pub fn search(&self, expr: &str, step: i32) -> Result<Option<Hdata<'a>>> {
+        if step == 0 {
+            return Err(Error::Other("hdata search step must not be 0".to_owned()));
+        }
+        let cexpr = CString::new(expr)?;
+        let ptr = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_search,
+                self.hdata_ptr,
+                self.data_ptr,
+                cexpr.as_ptr(),
+                step
+            )
+        };
+        if ptr.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(Hdata::new(self.plugin, self.hdata_ptr, ptr)))
+    }
+
+    fn check_pointer(&self, list: *mut c_void) -> bool {
+        unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_check_pointer,
+                self.hdata_ptr,
+                list,
+                self.data_ptr
+            ) != 0
+        }
+    }
+
+    // Check that the pointer is an element of the list starting at list, to
+    // make sure a stored pointer still points to a live object
+    pub fn is_valid_in(&self, list: &Hdata) -> bool {
+        self.check_pointer(list.data_ptr)
+    }
+
+    // Like is_valid_in, but returns an error to allow using ? on stored
+    // handles
+    pub fn validate_in(&self, list: &Hdata) -> CallResult {
+        if !self.is_valid_in(list) {
+            return Err(Error::InvalidPointer(format!(
+                "hdata object (0x{:x})",
+                self.data_ptr as usize
+            )));
+        }
+        Ok(())
+    }
+
+    // Check against the current head of a named list, like gui_buffers
+    fn is_valid_in_list(&self, list: &CStr) -> bool {
+        let head = unsafe {
+            call_attr!(
+                self.plugin.ptr,
+                hdata_get_list,
+                self.hdata_ptr,
+                list.as_ptr()
+            )
+        };
+        !head.is_null() && self.check_pointer(head)
+    }
+
+    // Iterate from this element to the end of its list. Elements are not
+    // checked, so the iterator must be used up before Weechat can free them
+    pub fn try_iter(&self) -> Result<HdataIterator<'a>> {
+        self.iter_with_check(None)
+    }
+
+    // Like try_iter, but each element is checked against the named list before
+    // it is read, so the iterator can be kept between callbacks. The iteration
+    // ends at the first element that has been freed since its neighbours can
+    // no longer be reached
+    pub fn try_iter_in(&self, list: &str) -> Result<HdataIterator<'a>> {
+        self.iter_with_check(Some(CString::new(list)?))
+    }
+
+    fn iter_with_check(&self, check_list: Option<CString>) -> Result<HdataIterator<'a>> {
+        let cnext = CString::new("var_next")?;
+        let var_next = unsafe {
+            CStr::from_ptr(try_ptr!(
+                call_attr!(
+                    self.plugin.ptr,
+                    hdata_get_string,
+                    self.hdata_ptr,
+                    cnext.as_ptr()
+                ),
+                "hdata_get_string"
+            ))
         };
 
         Ok(HdataIterator {
             next_hdata: Some(self.clone()),
             next_key: &var_next,
+            check_list,
         })
     }
 }
@@ -247,6 +679,11 @@ impl<'a> Iterator for HdataIterator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         let current = self.next_hdata.take();
         if let Some(ref c) = current {
+            if let Some(ref list) = self.check_list {
+                if !c.is_valid_in_list(list) {
+                    return None;
+                }
+            }
             self.next_hdata = match c.get_from_cstr(self.next_key) {
                 HdataValue::Hdata(hdata) => Some(hdata),
                 _ => None,
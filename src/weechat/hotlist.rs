@@ -0,0 +1,60 @@
+use super::{Buffer, CallResult, Error, Hdata, HotlistPriority, Plugin, Result};
+
+#[derive(Clone, Debug)]
+pub struct HotlistEntry<'a> {
+    pub buffer: Buffer<'a>,
+    // Highest priority of the unread messages
+    pub priority: HotlistPriority,
+    pub creation_time: libc::time_t,
+    counts: Vec<i32>,
+}
+
+impl<'a> HotlistEntry<'a> {
+    fn try_from_hdata(hdata: &Hdata<'a>) -> Result<Self> {
+        let priority = hdata.get_i32("priority")?;
+        Ok(Self {
+            buffer: Buffer::try_from_hdata(hdata.get_hdata("buffer")?)?,
+            priority: HotlistPriority::from_i32(priority)
+                .ok_or_else(|| Error::Other(format!("unknown hotlist priority {}", priority)))?,
+            creation_time: hdata.get_time("creation_time.tv_sec").unwrap_or(0),
+            counts: hdata.get_i32_array("count")?,
+        })
+    }
+
+    // Number of unread messages with the given priority
+    pub fn count(&self, priority: HotlistPriority) -> i32 {
+        self.counts.get(priority as usize).cloned().unwrap_or(0)
+    }
+
+    pub fn total_count(&self) -> i32 {
+        self.counts.iter().sum()
+    }
+
+    // Remove the buffer from the hotlist, like when switching to it
+    pub fn clear(&self) -> CallResult {
+        self.buffer.set_hotlist(None)
+    }
+}
+
+impl Plugin {
+    // Get the hotlist, sorted the way Weechat displays it
+    pub fn hotlist(&self) -> Result<Vec<HotlistEntry>> {
+        let first = match self.hdata_from_list("hotlist", "gui_hotlist") {
+            Ok(first) => first,
+            // The list pointer is null when the hotlist is empty
+            Err(Error::NotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        first
+            .try_iter()?
+            .map(|h| HotlistEntry::try_from_hdata(&h))
+            .collect()
+    }
+
+    pub fn hotlist_clear(&self) -> CallResult {
+        for entry in self.hotlist()? {
+            entry.clear()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use super::{Error, Hashtable, HashtableType, Plugin, Result};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+
+impl Plugin {
+    // Get an info by name, like "weechat_dir" or "irc_nick". Returns None if
+    // the info does not exist or has no value for the given arguments
+    pub fn info_get<'a, A>(&self, name: &str, args: A) -> Result<Option<String>>
+    where
+        A: Into<Option<&'a str>>,
+    {
+        let cname = CString::new(name)?;
+        let cargs = CString::new(args.into().unwrap_or(""))?;
+        let value =
+            unsafe { call_attr!(self.ptr, info_get, self.ptr, cname.as_ptr(), cargs.as_ptr()) };
+        if value.is_null() {
+            return Ok(None);
+        }
+
+        // The string is owned by Weechat and may be reused by the next call,
+        // so we copy it
+        Ok(Some(
+            unsafe { CStr::from_ptr(value as *const _) }
+                .to_str()?
+                .to_owned(),
+        ))
+    }
+
+    // Get an info that takes and returns a hashtable, like irc_message_parse
+    pub fn info_get_hashtable(&self, name: &str, args: &Hashtable) -> Result<Hashtable> {
+        let cname = CString::new(name)?;
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    info_get_hashtable,
+                    self.ptr,
+                    cname.as_ptr(),
+                    args.ptr
+                )
+            },
+            "info_get_hashtable"
+        );
+        Hashtable::from_owned_ptr(self, ptr)
+    }
+
+    // Weechat version as a number, e.g. 0x02030000 for 2.3
+    pub fn version_number(&self) -> Result<i32> {
+        let version = self
+            .info_get("version_number", None)?
+            .ok_or_else(|| Error::NotFound("info version_number".to_owned()))?;
+        version
+            .parse()
+            .map_err(|_| Error::Other(format!("invalid version number {}", version)))
+    }
+
+    // Split a raw IRC message into its parts, like "nick", "command",
+    // "channel" and "text". The server is used to decode channel names
+    pub fn irc_message_parse(
+        &self,
+        message: &str,
+        server: Option<&str>,
+    ) -> Result<HashMap<String, String>> {
+        let args = self.hashtable_new(HashtableType::Str, HashtableType::Str)?;
+        args.set("message", message)?;
+        if let Some(server) = server {
+            args.set("server", server)?;
+        }
+        self.info_get_hashtable("irc_message_parse", &args)?
+            .to_string_map()
+    }
+}
@@ -0,0 +1,303 @@
+use super::{Error, Plugin, Result};
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::ptr;
+use std::slice;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InfolistValue {
+    Integer(i32),
+    Str(String),
+    Pointer(*mut c_void),
+    Buffer(Vec<u8>),
+    Time(libc::time_t),
+}
+
+// A single item of an infolist. Values are copied out of Weechat so records
+// can be kept after the infolist has been freed
+#[derive(Clone, Debug, Default)]
+pub struct InfolistRecord {
+    values: HashMap<String, InfolistValue>,
+}
+
+// Iterator over the items of an infolist, which is freed when dropped
+#[derive(Debug)]
+pub struct Infolist<'a> {
+    plugin: &'a Plugin,
+    ptr: *mut ::ffi::t_infolist,
+}
+
+impl InfolistRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set<V: Into<InfolistValue>>(&mut self, name: &str, value: V) {
+        self.values.insert(name.to_owned(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InfolistValue> {
+        self.values.get(name)
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.values.keys().map(|k| k.as_str()).collect()
+    }
+
+    fn type_error(&self, name: &str, expected: &str) -> Error {
+        if !self.values.contains_key(name) {
+            return Error::NotFound(format!("infolist variable {}", name));
+        }
+        Error::TypeMismatch {
+            name: format!("infolist variable {}", name),
+            expected: expected.to_owned(),
+        }
+    }
+
+    pub fn get_i32(&self, name: &str) -> Result<i32> {
+        match self.get(name) {
+            Some(&InfolistValue::Integer(v)) => Ok(v),
+            _ => Err(self.type_error(name, "integer")),
+        }
+    }
+
+    pub fn get_str(&self, name: &str) -> Result<&str> {
+        match self.get(name) {
+            Some(&InfolistValue::Str(ref v)) => Ok(v),
+            _ => Err(self.type_error(name, "string")),
+        }
+    }
+
+    pub fn get_ptr(&self, name: &str) -> Result<*mut c_void> {
+        match self.get(name) {
+            Some(&InfolistValue::Pointer(v)) => Ok(v),
+            _ => Err(self.type_error(name, "pointer")),
+        }
+    }
+
+    pub fn get_buffer(&self, name: &str) -> Result<&[u8]> {
+        match self.get(name) {
+            Some(&InfolistValue::Buffer(ref v)) => Ok(v),
+            _ => Err(self.type_error(name, "buffer")),
+        }
+    }
+
+    pub fn get_time(&self, name: &str) -> Result<libc::time_t> {
+        match self.get(name) {
+            Some(&InfolistValue::Time(v)) => Ok(v),
+            _ => Err(self.type_error(name, "time")),
+        }
+    }
+}
+
+impl From<i32> for InfolistValue {
+    fn from(value: i32) -> Self {
+        InfolistValue::Integer(value)
+    }
+}
+
+impl<'a> From<&'a str> for InfolistValue {
+    fn from(value: &'a str) -> Self {
+        InfolistValue::Str(value.to_owned())
+    }
+}
+
+impl From<String> for InfolistValue {
+    fn from(value: String) -> Self {
+        InfolistValue::Str(value)
+    }
+}
+
+// Build a Weechat infolist from records. The caller is responsible for
+// freeing the returned infolist, usually by handing it over to Weechat
+pub(crate) fn infolist_from_records(
+    plugin: &Plugin,
+    records: &[InfolistRecord],
+) -> Result<*mut ::ffi::t_infolist> {
+    let infolist = try_ptr!(
+        unsafe { call_attr!(plugin.ptr, infolist_new, plugin.ptr) },
+        "infolist_new"
+    );
+    let result = (|| -> Result<()> {
+        for record in records {
+            let item = try_ptr!(
+                unsafe { call_attr!(plugin.ptr, infolist_new_item, infolist) },
+                "infolist_new_item"
+            );
+            for (name, value) in &record.values {
+                let cname = CString::new(name.as_str())?;
+                let var = unsafe {
+                    match *value {
+                        InfolistValue::Integer(v) => {
+                            call_attr!(
+                                plugin.ptr,
+                                infolist_new_var_integer,
+                                item,
+                                cname.as_ptr(),
+                                v
+                            )
+                        },
+                        InfolistValue::Str(ref v) => {
+                            let cvalue = CString::new(v.as_str())?;
+                            call_attr!(
+                                plugin.ptr,
+                                infolist_new_var_string,
+                                item,
+                                cname.as_ptr(),
+                                cvalue.as_ptr()
+                            )
+                        },
+                        InfolistValue::Pointer(v) => {
+                            call_attr!(
+                                plugin.ptr,
+                                infolist_new_var_pointer,
+                                item,
+                                cname.as_ptr(),
+                                v
+                            )
+                        },
+                        InfolistValue::Buffer(ref v) => call_attr!(
+                            plugin.ptr,
+                            infolist_new_var_buffer,
+                            item,
+                            cname.as_ptr(),
+                            v.as_ptr() as *mut c_void,
+                            v.len() as i32
+                        ),
+                        InfolistValue::Time(v) => {
+                            call_attr!(plugin.ptr, infolist_new_var_time, item, cname.as_ptr(), v)
+                        },
+                    }
+                };
+                try_ptr!(var, "infolist_new_var");
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        unsafe { call_attr!(plugin.ptr, infolist_free, infolist) };
+        return Err(e);
+    }
+    Ok(infolist)
+}
+
+impl<'a> Infolist<'a> {
+    // Read all variables of the current item. Weechat describes them as a
+    // comma separated list of "type:name", e.g. "i:number,s:name"
+    fn read_record(&self) -> Result<InfolistRecord> {
+        let fields = unsafe {
+            CStr::from_ptr(try_ptr!(
+                call_attr!(self.plugin.ptr, infolist_fields, self.ptr),
+                "infolist_fields"
+            ))
+        }
+        .to_str()?;
+
+        let mut record = InfolistRecord::default();
+        for field in fields.split(',').filter(|f| !f.is_empty()) {
+            let (var_type, name) = match field.find(':') {
+                Some(i) => (&field[..i], &field[i + 1..]),
+                None => continue,
+            };
+            let cname = CString::new(name)?;
+            let value = unsafe {
+                match var_type {
+                    "i" => InfolistValue::Integer(call_attr!(
+                        self.plugin.ptr,
+                        infolist_integer,
+                        self.ptr,
+                        cname.as_ptr()
+                    )),
+                    "s" => {
+                        let s =
+                            call_attr!(self.plugin.ptr, infolist_string, self.ptr, cname.as_ptr());
+                        if s.is_null() {
+                            InfolistValue::Str(String::new())
+                        } else {
+                            InfolistValue::Str(CStr::from_ptr(s).to_string_lossy().into_owned())
+                        }
+                    },
+                    "p" => InfolistValue::Pointer(call_attr!(
+                        self.plugin.ptr,
+                        infolist_pointer,
+                        self.ptr,
+                        cname.as_ptr()
+                    )),
+                    "b" => {
+                        let mut size = 0;
+                        let data = call_attr!(
+                            self.plugin.ptr,
+                            infolist_buffer,
+                            self.ptr,
+                            cname.as_ptr(),
+                            &mut size
+                        );
+                        if data.is_null() || size <= 0 {
+                            InfolistValue::Buffer(Vec::new())
+                        } else {
+                            InfolistValue::Buffer(
+                                slice::from_raw_parts(data as *const u8, size as usize).to_vec(),
+                            )
+                        }
+                    },
+                    "t" => InfolistValue::Time(call_attr!(
+                        self.plugin.ptr,
+                        infolist_time,
+                        self.ptr,
+                        cname.as_ptr()
+                    )),
+                    _ => continue,
+                }
+            };
+            record.values.insert(name.to_owned(), value);
+        }
+        Ok(record)
+    }
+}
+
+impl<'a> Iterator for Infolist<'a> {
+    type Item = Result<InfolistRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if unsafe { call_attr!(self.plugin.ptr, infolist_next, self.ptr) } == 0 {
+            return None;
+        }
+        Some(self.read_record())
+    }
+}
+
+impl<'a> Drop for Infolist<'a> {
+    fn drop(&mut self) {
+        unsafe { call_attr!(self.plugin.ptr, infolist_free, self.ptr) };
+    }
+}
+
+impl Plugin {
+    // Get an infolist by name, like "buffer" or "irc_server". Some infolists
+    // accept a pointer to a single item and arguments to filter the items
+    pub fn infolist_get<'a, A>(&self, name: &str, pointer: *mut c_void, args: A) -> Result<Infolist>
+    where
+        A: Into<Option<&'a str>>,
+    {
+        let cname = CString::new(name)?;
+        let cargs = match args.into() {
+            Some(args) => Some(CString::new(args)?),
+            None => None,
+        };
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    infolist_get,
+                    self.ptr,
+                    cname.as_ptr(),
+                    pointer,
+                    cargs.as_ref().map_or(ptr::null(), |a| a.as_ptr())
+                )
+            },
+            "infolist_get"
+        );
+        Ok(Infolist { plugin: self, ptr })
+    }
+}
@@ -0,0 +1,90 @@
+use super::{Buffer, Hdata, HdataValue, Result};
+
+#[derive(Clone, Debug)]
+pub struct Line<'a> {
+    pub date: libc::time_t,
+    pub date_printed: libc::time_t,
+    pub prefix: &'a str,
+    pub message: &'a str,
+    pub tags: Vec<&'a str>,
+    pub highlight: bool,
+    pub displayed: bool,
+    // Nick of the sender, taken from the nick_* tag
+    pub nick: Option<&'a str>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LineIterator<'a> {
+    // Lines are freed when their buffer is closed, cleared or trimmed, so we
+    // check the buffer and its list of lines before following them
+    pub(crate) buffer: Buffer<'a>,
+    pub(crate) next_key: String,
+    pub(crate) next_hdata: Option<Hdata<'a>>,
+}
+
+impl<'a> Line<'a> {
+    // Build a line from a line hdata. Fields that are missing are left empty
+    // rather than failing, since not all buffers fill in all fields
+    pub fn try_from_hdata(hdata: &Hdata<'a>) -> Result<Self> {
+        let data = hdata.get_hdata("data")?;
+        // A single empty or invalid tag should not hide all the others
+        let tags: Vec<&str> = data
+            .get_array("tags_array")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| match v {
+                HdataValue::Str(tag) => tag.to_str().ok(),
+                _ => None,
+            })
+            .collect();
+        let nick = tags
+            .iter()
+            .cloned()
+            .find(|t| t.starts_with("nick_"))
+            .map(|t| &t["nick_".len()..]);
+
+        Ok(Self {
+            date: data.get_time("date").unwrap_or(0),
+            date_printed: data.get_time("date_printed").unwrap_or(0),
+            prefix: data.get_str("prefix").unwrap_or(""),
+            message: data.get_str("message").unwrap_or(""),
+            highlight: data.get_i8("highlight").unwrap_or(0) != 0,
+            displayed: data.get_i8("displayed").unwrap_or(0) != 0,
+            nick,
+            tags,
+        })
+    }
+}
+
+impl<'a> LineIterator<'a> {
+    fn is_line_valid(&self, line: &Hdata) -> bool {
+        match self
+            .buffer
+            .hdata
+            .get_hdata("lines")
+            .and_then(|lines| lines.get_hdata("first_line"))
+        {
+            Ok(first_line) => line.is_valid_in(&first_line),
+            Err(_) => false,
+        }
+    }
+}
+
+impl<'a> Iterator for LineIterator<'a> {
+    type Item = Line<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let current = self.next_hdata.take()?;
+            if !self.buffer.is_valid() || !self.is_line_valid(&current) {
+                return None;
+            }
+            self.next_hdata = current.get_hdata(&self.next_key).ok();
+
+            // Lines without data are skipped instead of ending the iteration
+            // early
+            if let Ok(line) = Line::try_from_hdata(&current) {
+                return Some(line);
+            }
+        }
+    }
+}
@@ -10,11 +10,11 @@ macro_rules! call_attr {
 
 #[macro_export]
 macro_rules! try_ptr {
-    ($ptr:expr) => {{
+    ($ptr:expr, $call:expr) => {{
         // We must evaluate $ptr here or we will run the expression twice
         let ptr = $ptr;
         if ptr.is_null() {
-            return Err(());
+            return Err(::weechat::Error::NullPointer($call));
         } else {
             ptr
         }
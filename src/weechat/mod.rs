@@ -1,7 +1,11 @@
 #![allow(dead_code)]
 
 use ffi;
-use std::ffi::{c_void, CString};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::os::unix::io::RawFd;
 use std::ptr;
 use std::time::Duration;
 
@@ -9,12 +13,41 @@ use std::time::Duration;
 mod macros;
 
 mod callbacks;
+mod completion;
+mod config;
+mod error;
+mod from_hdata;
+mod hashtable;
 mod hdata;
+mod hotlist;
+mod info;
+mod infolist;
+mod line;
+mod nicklist;
+mod panic;
+mod process;
+mod window;
 
-use self::callbacks::{malloc_callback, CommandHook, TimerHook};
-pub use self::hdata::Hdata;
+use self::callbacks::{
+    malloc_callback, BufferCloseHook, BufferInputHook, BufferState, CommandHook, CompletionHook,
+    FdHook, InfoHashtableHook, InfoHook, InfoState, InfolistHook, ModifierHook, ProcessHook,
+    ProcessState, TimerHook,
+};
+pub use self::completion::{Completion, CompletionPosition};
+pub use self::config::{Config, ConfigOption, ConfigSection};
+pub use self::error::Error;
+pub use self::from_hdata::{validate_var, FromHdata, HdataField};
+pub use self::hashtable::{Hashtable, HashtableType, HashtableValue};
+pub use self::hdata::{Hdata, HdataIterator, HdataValue};
+pub use self::hotlist::HotlistEntry;
+pub use self::infolist::{Infolist, InfolistRecord, InfolistValue};
+pub use self::line::{Line, LineIterator};
+pub use self::nicklist::{Nick, NicklistGroup, NicklistItem, NicklistIterator};
+pub use self::panic::{catch_panic, restore_panic_hook};
+pub use self::process::{ProcessCommand, ProcessOutput};
+pub use self::window::{Window, WindowIterator};
 
-pub type Result<T> = std::result::Result<T, ()>;
+pub type Result<T> = std::result::Result<T, Error>;
 
 pub type CallResult = Result<()>;
 
@@ -23,7 +56,64 @@ pub struct Plugin {
     pub ptr: *mut ffi::t_weechat_plugin,
 }
 
-type Hook = *mut ffi::t_hook;
+pub type Hook = *mut ffi::t_hook;
+
+// Conditions to watch a file descriptor for when using Plugin::hook_fd
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FdInterest {
+    pub read: bool,
+    pub write: bool,
+    pub exception: bool,
+}
+
+// Formatted buffers hold lines like chat buffers, while free content buffers
+// are a grid of lines that can be set individually
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferType {
+    Formatted,
+    Free,
+}
+
+// Which messages add a buffer to the hotlist
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotifyLevel {
+    None,
+    Highlight,
+    Message,
+    All,
+}
+
+impl NotifyLevel {
+    fn from_i32(level: i32) -> Option<Self> {
+        match level {
+            0 => Some(NotifyLevel::None),
+            1 => Some(NotifyLevel::Highlight),
+            2 => Some(NotifyLevel::Message),
+            3 => Some(NotifyLevel::All),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HotlistPriority {
+    Low,
+    Message,
+    Private,
+    Highlight,
+}
+
+impl HotlistPriority {
+    fn from_i32(priority: i32) -> Option<Self> {
+        match priority {
+            0 => Some(HotlistPriority::Low),
+            1 => Some(HotlistPriority::Message),
+            2 => Some(HotlistPriority::Private),
+            3 => Some(HotlistPriority::Highlight),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Buffer<'a> {
@@ -31,40 +121,192 @@ pub struct Buffer<'a> {
     ptr: *mut ffi::t_gui_buffer,
 }
 
-#[derive(Clone, Debug)]
-pub struct LineIterator<'a> {
-    next_key: String,
-    next_hdata: Option<Hdata<'a>>,
-}
-
 impl<'a> Buffer<'a> {
     pub fn try_from_hdata(hdata: Hdata<'a>) -> Result<Self> {
-        // Check if the pointer is a gui buffer pointer
-        let buffer_list = hdata.plugin.hdata_from_list("buffer", "gui_buffers")?;
-        if unsafe {
-            call_attr!(
-                hdata.plugin.ptr,
-                hdata_check_pointer,
-                buffer_list.hdata_ptr,
-                buffer_list.data_ptr,
-                hdata.data_ptr
-            )
-        } == 0
-        {
-            return Err(());
-        }
-        Ok(Self {
+        let buffer = Self {
             ptr: hdata.data_ptr as *mut ffi::t_gui_buffer,
             hdata,
-        })
+        };
+        buffer.validate()?;
+        Ok(buffer)
+    }
+
+    // Check that the buffer has not been closed. Buffers may be closed at any
+    // time between callbacks, so stored buffers must be checked before use
+    pub fn is_valid(&self) -> bool {
+        match self.hdata.plugin.hdata_from_list("buffer", "gui_buffers") {
+            Ok(buffer_list) => self.hdata.is_valid_in(&buffer_list),
+            Err(_) => false,
+        }
+    }
+
+    pub fn validate(&self) -> CallResult {
+        if !self.is_valid() {
+            return Err(Error::InvalidPointer("buffer".to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn set(&self, property: &str, value: &str) -> CallResult {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        let cvalue = CString::new(value)?;
+        unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                buffer_set,
+                self.ptr,
+                cproperty.as_ptr(),
+                cvalue.as_ptr()
+            );
+        }
+        Ok(())
+    }
+
+    // Remove all lines from the buffer
+    pub fn clear(&self) -> CallResult {
+        self.validate()?;
+        unsafe { call_attr!(self.hdata.plugin.ptr, buffer_clear, self.ptr) };
+        Ok(())
+    }
+
+    // Close the buffer. This calls the close callback if one was given when
+    // creating the buffer
+    pub fn close(self) -> CallResult {
+        self.validate()?;
+        unsafe { call_attr!(self.hdata.plugin.ptr, buffer_close, self.ptr) };
+        Ok(())
     }
 
     pub fn get_name(&self) -> Result<&'a str> {
+        self.validate()?;
         self.hdata.get_str("name")
     }
 
+    pub fn get_string(&self, property: &str) -> Result<&'a str> {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        let value = unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                buffer_get_string,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        };
+        if value.is_null() {
+            return Err(Error::NotFound(format!("buffer property {}", property)));
+        }
+        Ok(unsafe { CStr::from_ptr(value) }.to_str()?)
+    }
+
+    pub fn get_integer(&self, property: &str) -> Result<i32> {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        Ok(unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                buffer_get_integer,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        })
+    }
+
+    pub fn get_pointer(&self, property: &str) -> Result<*mut c_void> {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        Ok(unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                buffer_get_pointer,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        })
+    }
+
+    pub fn get_short_name(&self) -> Result<&'a str> {
+        self.get_string("short_name")
+    }
+
+    pub fn set_short_name(&self, short_name: &str) -> CallResult {
+        self.set("short_name", short_name)
+    }
+
+    // Name including the plugin, e.g. "irc.libera.#rust"
+    pub fn get_full_name(&self) -> Result<&'a str> {
+        self.get_string("full_name")
+    }
+
+    // Name of the plugin that owns the buffer, "core" for Weechat's own
+    pub fn get_plugin_name(&self) -> Result<&'a str> {
+        self.get_string("plugin")
+    }
+
+    pub fn get_type(&self) -> Result<BufferType> {
+        match self.get_integer("type")? {
+            1 => Ok(BufferType::Free),
+            _ => Ok(BufferType::Formatted),
+        }
+    }
+
+    pub fn get_title(&self) -> Result<&'a str> {
+        self.get_string("title")
+    }
+
+    pub fn set_title(&self, title: &str) -> CallResult {
+        self.set("title", title)
+    }
+
+    pub fn get_localvar(&self, name: &str) -> Result<Option<&'a str>> {
+        match self.get_string(&format!("localvar_{}", name)) {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_localvar(&self, name: &str, value: &str) -> CallResult {
+        self.set(&format!("localvar_set_{}", name), value)
+    }
+
+    pub fn del_localvar(&self, name: &str) -> CallResult {
+        self.set(&format!("localvar_del_{}", name), "")
+    }
+
+    pub fn get_localvars(&self) -> Result<HashMap<String, String>> {
+        self.validate()?;
+        self.hdata.get_hashtable("local_variables")?.to_string_map()
+    }
+
+    // Private buffers are queries with a single user, like IRC private
+    // messages
+    pub fn is_private(&self) -> Result<bool> {
+        Ok(self.get_localvar("type")? == Some("private"))
+    }
+
+    pub fn get_notify_level(&self) -> Result<NotifyLevel> {
+        let level = self.get_integer("notify")?;
+        NotifyLevel::from_i32(level).ok_or_else(|| Error::ReturnCode("buffer_get_integer", level))
+    }
+
+    pub fn set_notify_level(&self, level: NotifyLevel) -> CallResult {
+        self.set("notify", &(level as i32).to_string())
+    }
+
+    // Add the buffer to the hotlist with the given priority, or remove it
+    // from the hotlist when the priority is None
+    pub fn set_hotlist(&self, priority: Option<HotlistPriority>) -> CallResult {
+        match priority {
+            Some(priority) => self.set("hotlist", &(priority as i32).to_string()),
+            None => self.set("hotlist", "-1"),
+        }
+    }
+
     pub fn command(&self, cmd: &str) -> CallResult {
-        let ccmd = CString::new(cmd).unwrap();
+        self.validate()?;
+        let ccmd = CString::new(cmd)?;
         let result = unsafe {
             call_attr!(
                 self.hdata.plugin.ptr,
@@ -76,54 +318,102 @@ impl<'a> Buffer<'a> {
         };
         match result {
             ffi::WEECHAT_RC_OK => Ok(()),
-            ffi::WEECHAT_RC_ERROR => Err(()),
-            _ => unreachable!(),
+            _ => Err(Error::ReturnCode("command", result)),
         }
     }
 
+    // Send text to the buffer as if the user had typed it, which passes it to
+    // the buffer's input callback. Each line is sent as a separate message
+    // since Weechat input can't contain newlines. Lines are never run as
+    // commands
+    pub fn send_input(&self, text: &str) -> CallResult {
+        let mut command_chars = String::from("/");
+        if let Ok(option) = self.hdata.plugin.config_get("weechat.look.command_chars") {
+            command_chars.push_str(&option.string()?);
+        }
+
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            // Weechat sends input starting with a doubled command char as text
+            // with one of the chars removed
+            match line.chars().next() {
+                Some(c) if command_chars.contains(c) => {
+                    let mut escaped = String::with_capacity(line.len() + c.len_utf8());
+                    escaped.push(c);
+                    escaped.push_str(line);
+                    self.command(&escaped)?;
+                },
+                _ => self.command(line)?,
+            }
+        }
+        Ok(())
+    }
+
     pub fn print(&self, msg: &str) {
-        let cmsg = CString::new(msg).unwrap();
-        unsafe {
-            call_attr!(
-                self.hdata.plugin.ptr,
-                printf_date_tags,
-                self.ptr,
-                0,
-                ptr::null(),
-                cmsg.as_ptr()
-            );
+        // Printing to a closed buffer is silently ignored
+        if self.is_valid() {
+            self.hdata.plugin.print_to(self.ptr, msg);
         }
     }
 
+    // Print an error to this buffer, prefixed with the plugin name
+    pub fn print_error(&self, err: &Error) {
+        self.print(&self.hdata.plugin.format_error(err));
+    }
+
     pub fn iter_lines_from_top(&self) -> Result<LineIterator<'a>> {
+        self.validate()?;
         Ok(LineIterator {
+            buffer: self.clone(),
             next_key: "next_line".to_owned(),
             next_hdata: self.hdata.get_hdata("lines")?.get_hdata("first_line").ok(),
         })
     }
 
     pub fn iter_lines_from_bottom(&self) -> Result<LineIterator<'a>> {
+        self.validate()?;
         Ok(LineIterator {
+            buffer: self.clone(),
             next_key: "prev_line".to_owned(),
             next_hdata: self.hdata.get_hdata("lines")?.get_hdata("last_line").ok(),
         })
     }
-}
 
-impl<'a> Iterator for LineIterator<'a> {
-    type Item = &'a str;
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = self.next_hdata.take();
-        self.next_hdata = match current {
-            Some(ref c) => c.get_hdata(&self.next_key).ok(),
-            None => None,
+    // Iterate over the last count lines, oldest first
+    pub fn iter_last_lines(&self, count: usize) -> Result<LineIterator<'a>> {
+        self.validate()?;
+        let lines = self.hdata.get_hdata("lines")?;
+        let start = match (count, lines.get_hdata("last_line")) {
+            (0, _) | (_, Err(_)) => None,
+            (_, Ok(last)) => {
+                let back = std::cmp::min(count - 1, i32::max_value() as usize) as i32;
+                // Buffers with fewer lines than requested start from the top
+                last.move_by(-back)
+                    .or_else(|| lines.get_hdata("first_line").ok())
+            },
         };
+        Ok(LineIterator {
+            buffer: self.clone(),
+            next_key: "next_line".to_owned(),
+            next_hdata: start,
+        })
+    }
+}
 
-        if let Some(c) = current {
-            Some(c.get_hdata("data").unwrap().get_str("message").unwrap())
-        } else {
-            None
-        }
+#[derive(Clone, Debug)]
+pub struct BufferIterator<'a> {
+    inner: HdataIterator<'a>,
+}
+
+impl<'a> Iterator for BufferIterator<'a> {
+    type Item = Buffer<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The inner iterator has already checked the buffer against
+        // gui_buffers, so there is no need to validate it again
+        self.inner.next().map(|hdata| Buffer {
+            ptr: hdata.data_ptr as *mut ffi::t_gui_buffer,
+            hdata,
+        })
     }
 }
 
@@ -133,19 +423,51 @@ impl Plugin {
     }
 
     pub fn print(&self, msg: &str) {
-        let cmsg = CString::new(msg).unwrap();
+        self.print_to(ptr::null_mut(), msg);
+    }
+
+    fn print_to(&self, buffer: *mut ffi::t_gui_buffer, msg: &str) {
+        // Strip nul bytes rather than failing since printing is used for
+        // error reporting. The message is passed as an argument to not have
+        // Weechat interpret it as a format string
+        let cmsg = CString::new(msg.replace('\0', "")).unwrap_or_default();
         unsafe {
             call_attr!(
                 self.ptr,
                 printf_date_tags,
-                ptr::null_mut(),
+                buffer,
                 0,
                 ptr::null(),
+                b"%s\0".as_ptr() as *const _,
                 cmsg.as_ptr()
             );
         }
     }
 
+    // Print an error to the core buffer, prefixed with the plugin name
+    pub fn print_error(&self, err: &Error) {
+        self.print(&self.format_error(err));
+    }
+
+    fn format_error(&self, err: &Error) -> String {
+        let (prefix, name) = unsafe {
+            (
+                CStr::from_ptr(call_attr!(
+                    self.ptr,
+                    prefix,
+                    b"error\0".as_ptr() as *const _
+                )),
+                CStr::from_ptr((*self.ptr).name),
+            )
+        };
+        format!(
+            "{}{}: {}",
+            prefix.to_string_lossy(),
+            name.to_string_lossy(),
+            err
+        )
+    }
+
     pub fn debug_print(&self, level: i32, msg: &str) {
         if unsafe { !(*self.ptr).debug >= level } {
             return;
@@ -168,27 +490,30 @@ impl Plugin {
         H: Into<Option<&'a str>>,
         C: Into<Option<&'a str>>,
     {
-        let ccmd = CString::new(cmd).or(Err(()))?;
-        let cdescription = CString::new(description.into().unwrap_or("")).or(Err(()))?;
-        let cargs = CString::new(args.into().unwrap_or("")).or(Err(()))?;
-        let cargs_help = CString::new(args_help.into().unwrap_or("")).or(Err(()))?;
-        let ccompletion = CString::new(completion.into().unwrap_or("")).or(Err(()))?;
+        let ccmd = CString::new(cmd)?;
+        let cdescription = CString::new(description.into().unwrap_or(""))?;
+        let cargs = CString::new(args.into().unwrap_or(""))?;
+        let cargs_help = CString::new(args_help.into().unwrap_or(""))?;
+        let ccompletion = CString::new(completion.into().unwrap_or(""))?;
 
-        Ok(try_ptr!(unsafe {
-            call_attr!(
-                self.ptr,
-                hook_command,
-                self.ptr,
-                ccmd.as_ptr(),
-                cdescription.as_ptr(),
-                cargs.as_ptr(),
-                cargs_help.as_ptr(),
-                ccompletion.as_ptr(),
-                Some(callbacks::hook_command),
-                self.ptr as *const c_void,
-                malloc_callback(callback)? as *mut c_void
-            )
-        }))
+        Ok(try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_command,
+                    self.ptr,
+                    ccmd.as_ptr(),
+                    cdescription.as_ptr(),
+                    cargs.as_ptr(),
+                    cargs_help.as_ptr(),
+                    ccompletion.as_ptr(),
+                    Some(callbacks::hook_command),
+                    self.ptr as *const c_void,
+                    malloc_callback(callback)? as *mut c_void
+                )
+            },
+            "hook_command"
+        ))
     }
 
     pub fn hook_timer(
@@ -197,23 +522,458 @@ impl Plugin {
         max_calls: i32,
         callback: TimerHook,
     ) -> Result<Hook> {
-        Ok(try_ptr!(unsafe {
+        Ok(try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_timer,
+                    self.ptr,
+                    (1000 * interval.as_secs() + interval.subsec_millis() as u64) as i64,
+                    0,
+                    max_calls,
+                    Some(callbacks::hook_timer),
+                    self.ptr as *const c_void,
+                    malloc_callback(callback)? as *mut c_void
+                )
+            },
+            "hook_timer"
+        ))
+    }
+
+    pub fn hook_completion<'a, D>(
+        &self,
+        item: &str,
+        description: D,
+        callback: CompletionHook,
+    ) -> Result<Hook>
+    where
+        D: Into<Option<&'a str>>,
+    {
+        let citem = CString::new(item)?;
+        let cdescription = CString::new(description.into().unwrap_or(""))?;
+
+        Ok(try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_completion,
+                    self.ptr,
+                    citem.as_ptr(),
+                    cdescription.as_ptr(),
+                    Some(callbacks::hook_completion),
+                    self.ptr as *const c_void,
+                    malloc_callback(callback)? as *mut c_void
+                )
+            },
+            "hook_completion"
+        ))
+    }
+
+    // The callback may be a closure, which is dropped when unhooking
+    pub fn hook_fd<F>(&self, fd: RawFd, interest: FdInterest, callback: F) -> Result<Hook>
+    where
+        F: FnMut(&Plugin, RawFd) -> CallResult + 'static,
+    {
+        if !(interest.read || interest.write || interest.exception) {
+            return Err(Error::Other(
+                "hook_fd requires read, write or exception interest".to_owned(),
+            ));
+        }
+        let callback: Box<FdHook> = Box::new(callback);
+        let state = callbacks::keep_state(self, RefCell::new(callback));
+
+        let hook = unsafe {
             call_attr!(
                 self.ptr,
-                hook_timer,
+                hook_fd,
                 self.ptr,
-                (1000 * interval.as_secs() + interval.subsec_millis() as u64) as i64,
-                0,
-                max_calls,
-                Some(callbacks::hook_timer),
-                self.ptr as *const c_void,
-                malloc_callback(callback)? as *mut c_void
+                fd,
+                interest.read as i32,
+                interest.write as i32,
+                interest.exception as i32,
+                Some(callbacks::hook_fd),
+                state,
+                ptr::null_mut()
             )
-        }))
+        };
+        callbacks::attach_state(state, hook, "hook_fd")
     }
 
-    pub fn buffer_search_main(&self) -> Option<Buffer> {
-        let ptr = unsafe { call_attr!(self.ptr, buffer_search_main) };
+    // Publish an info that other plugins and scripts can read using info_get
+    // or ${info:name} in evaluated expressions
+    pub fn hook_info<'a, D, A>(
+        &self,
+        name: &str,
+        description: D,
+        args_description: A,
+        callback: InfoHook,
+    ) -> Result<Hook>
+    where
+        D: Into<Option<&'a str>>,
+        A: Into<Option<&'a str>>,
+    {
+        let cname = CString::new(name)?;
+        let cdescription = CString::new(description.into().unwrap_or(""))?;
+        let cargs_description = CString::new(args_description.into().unwrap_or(""))?;
+        let state = callbacks::keep_state(self, RefCell::new(InfoState::new(callback)));
+
+        let hook = unsafe {
+            call_attr!(
+                self.ptr,
+                hook_info,
+                self.ptr,
+                cname.as_ptr(),
+                cdescription.as_ptr(),
+                cargs_description.as_ptr(),
+                Some(callbacks::hook_info),
+                state,
+                ptr::null_mut()
+            )
+        };
+        callbacks::attach_state(state, hook, "hook_info")
+    }
+
+    pub fn hook_info_hashtable<'a, D, A, O>(
+        &self,
+        name: &str,
+        description: D,
+        args_description: A,
+        output_description: O,
+        callback: InfoHashtableHook,
+    ) -> Result<Hook>
+    where
+        D: Into<Option<&'a str>>,
+        A: Into<Option<&'a str>>,
+        O: Into<Option<&'a str>>,
+    {
+        let cname = CString::new(name)?;
+        let cdescription = CString::new(description.into().unwrap_or(""))?;
+        let cargs_description = CString::new(args_description.into().unwrap_or(""))?;
+        let coutput_description = CString::new(output_description.into().unwrap_or(""))?;
+
+        Ok(try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_info_hashtable,
+                    self.ptr,
+                    cname.as_ptr(),
+                    cdescription.as_ptr(),
+                    cargs_description.as_ptr(),
+                    coutput_description.as_ptr(),
+                    Some(callbacks::hook_info_hashtable),
+                    self.ptr as *const c_void,
+                    malloc_callback(callback)? as *mut c_void
+                )
+            },
+            "hook_info_hashtable"
+        ))
+    }
+
+    pub fn hook_infolist<'a, D, P, A>(
+        &self,
+        name: &str,
+        description: D,
+        pointer_description: P,
+        args_description: A,
+        callback: InfolistHook,
+    ) -> Result<Hook>
+    where
+        D: Into<Option<&'a str>>,
+        P: Into<Option<&'a str>>,
+        A: Into<Option<&'a str>>,
+    {
+        let cname = CString::new(name)?;
+        let cdescription = CString::new(description.into().unwrap_or(""))?;
+        let cpointer_description = CString::new(pointer_description.into().unwrap_or(""))?;
+        let cargs_description = CString::new(args_description.into().unwrap_or(""))?;
+
+        Ok(try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_infolist,
+                    self.ptr,
+                    cname.as_ptr(),
+                    cdescription.as_ptr(),
+                    cpointer_description.as_ptr(),
+                    cargs_description.as_ptr(),
+                    Some(callbacks::hook_infolist),
+                    self.ptr as *const c_void,
+                    malloc_callback(callback)? as *mut c_void
+                )
+            },
+            "hook_infolist"
+        ))
+    }
+
+    pub fn unhook(&self, hook: Hook) {
+        unsafe { call_attr!(self.ptr, unhook, hook) }
+        callbacks::drop_hook_states(hook);
+    }
+
+    // The callback may be a closure, which is dropped when unhooking
+    pub fn hook_modifier<F>(&self, modifier: &str, callback: F) -> Result<Hook>
+    where
+        F: FnMut(&Plugin, &str, &str, &str) -> Result<String> + 'static,
+    {
+        let cmodifier = CString::new(modifier)?;
+        let callback: Box<ModifierHook> = Box::new(callback);
+        let state = callbacks::keep_state(self, RefCell::new(callback));
+
+        let hook = unsafe {
+            call_attr!(
+                self.ptr,
+                hook_modifier,
+                self.ptr,
+                cmodifier.as_ptr(),
+                Some(callbacks::hook_modifier),
+                state,
+                ptr::null_mut()
+            )
+        };
+        callbacks::attach_state(state, hook, "hook_modifier")
+    }
+
+    pub fn hook_modifier_exec(
+        &self,
+        modifier: &str,
+        modifier_data: &str,
+        string: &str,
+    ) -> Result<String> {
+        let cmodifier = CString::new(modifier)?;
+        let cmodifier_data = CString::new(modifier_data)?;
+        let cstring = CString::new(string)?;
+
+        let result_ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_modifier_exec,
+                    self.ptr,
+                    cmodifier.as_ptr(),
+                    cmodifier_data.as_ptr(),
+                    cstring.as_ptr()
+                )
+            },
+            "hook_modifier_exec"
+        );
+
+        // The returned string is allocated by Weechat and must be freed by us
+        let result = unsafe { CStr::from_ptr(result_ptr) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { libc::free(result_ptr as *mut c_void) };
+        Ok(result)
+    }
+
+    // The callback may be a closure, which is dropped once the process has
+    // ended or when unhooking
+    pub fn hook_process<F>(&self, command: &str, timeout: Duration, callback: F) -> Result<Hook>
+    where
+        F: FnMut(&Plugin, ProcessOutput) -> CallResult + 'static,
+    {
+        let ccommand = CString::new(command)?;
+        let callback: Box<ProcessHook> = Box::new(callback);
+        let state = callbacks::keep_state(self, RefCell::new(ProcessState::new(callback)));
+
+        let hook = unsafe {
+            call_attr!(
+                self.ptr,
+                hook_process,
+                self.ptr,
+                ccommand.as_ptr(),
+                (1000 * timeout.as_secs() + timeout.subsec_millis() as u64) as i32,
+                Some(callbacks::hook_process),
+                state,
+                ptr::null_mut()
+            )
+        };
+        callbacks::attach_state(state, hook, "hook_process")
+    }
+
+    pub fn hook_process_hashtable<F>(
+        &self,
+        command: &str,
+        options: &Hashtable,
+        timeout: Duration,
+        callback: F,
+    ) -> Result<Hook>
+    where
+        F: FnMut(&Plugin, ProcessOutput) -> CallResult + 'static,
+    {
+        let ccommand = CString::new(command)?;
+        let callback: Box<ProcessHook> = Box::new(callback);
+        let state = callbacks::keep_state(self, RefCell::new(ProcessState::new(callback)));
+
+        let hook = unsafe {
+            call_attr!(
+                self.ptr,
+                hook_process_hashtable,
+                self.ptr,
+                ccommand.as_ptr(),
+                options.ptr,
+                (1000 * timeout.as_secs() + timeout.subsec_millis() as u64) as i32,
+                Some(callbacks::hook_process),
+                state,
+                ptr::null_mut()
+            )
+        };
+        callbacks::attach_state(state, hook, "hook_process_hashtable")
+    }
+
+    pub fn run_process<F>(&self, process: &ProcessCommand, callback: F) -> Result<Hook>
+    where
+        F: FnMut(&Plugin, ProcessOutput) -> CallResult + 'static,
+    {
+        let options = process.to_options(self)?;
+        let hook = self.hook_process_hashtable(
+            process.get_command(),
+            &options,
+            process.get_timeout(),
+            callback,
+        )?;
+
+        if let Some(stdin) = process.get_stdin() {
+            let cstdin_key = CString::new("stdin")?;
+            let cstdin_close_key = CString::new("stdin_close")?;
+            let cstdin = CString::new(stdin)?;
+            let cstdin_close = CString::new("1")?;
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    hook_set,
+                    hook,
+                    cstdin_key.as_ptr(),
+                    cstdin.as_ptr()
+                );
+                call_attr!(
+                    self.ptr,
+                    hook_set,
+                    hook,
+                    cstdin_close_key.as_ptr(),
+                    cstdin_close.as_ptr()
+                );
+            }
+        }
+
+        Ok(hook)
+    }
+
+    pub fn config_new(&self, name: &str) -> Result<Config> {
+        let cname = CString::new(name)?;
+
+        // Without a reload callback Weechat will simply reread the file when
+        // /reload is used
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.ptr,
+                    config_new,
+                    self.ptr,
+                    cname.as_ptr(),
+                    None,
+                    ptr::null(),
+                    ptr::null_mut()
+                )
+            },
+            "config_new"
+        );
+        Ok(Config::new(self, ptr))
+    }
+
+    pub fn config_search(&self, name: &str) -> Result<Config> {
+        for config_file in self
+            .hdata_from_list("config_file", "config_files")?
+            .try_iter()?
+        {
+            if config_file.get_str("name") == Ok(name) {
+                return Ok(Config::new(
+                    self,
+                    config_file.data_ptr as *mut ffi::t_config_file,
+                ));
+            }
+        }
+        Err(Error::NotFound(format!("configuration file {}", name)))
+    }
+
+    // Find an option by its full name, like weedesktop.general.away_message
+    pub fn config_get(&self, name: &str) -> Result<ConfigOption> {
+        let cname = CString::new(name)?;
+        let ptr = try_ptr!(
+            unsafe { call_attr!(self.ptr, config_get, cname.as_ptr()) },
+            "config_get"
+        );
+        Ok(ConfigOption::new(self, ptr))
+    }
+
+    // Callbacks may be closures, which are dropped when the buffer is closed
+    pub fn buffer_new<I, C>(
+        &self,
+        name: &str,
+        buffer_type: BufferType,
+        input: Option<I>,
+        close: Option<C>,
+    ) -> Result<Buffer>
+    where
+        I: FnMut(&Plugin, Buffer, &str) -> CallResult + 'static,
+        C: FnMut(&Plugin, Buffer) -> CallResult + 'static,
+    {
+        let cname = CString::new(name)?;
+        let input = input.map(|f| Box::new(f) as Box<BufferInputHook>);
+        let close = close.map(|f| Box::new(f) as Box<BufferCloseHook>);
+        let input_callback: Option<
+            unsafe extern "C" fn(
+                *const c_void,
+                *mut c_void,
+                *mut ffi::t_gui_buffer,
+                *const c_char,
+            ) -> i32,
+        > = match input {
+            Some(_) => Some(callbacks::buffer_input),
+            None => None,
+        };
+
+        // The close callback is needed whenever there is state to drop
+        let close_callback: Option<
+            unsafe extern "C" fn(*const c_void, *mut c_void, *mut ffi::t_gui_buffer) -> i32,
+        > = if input.is_some() || close.is_some() {
+            Some(callbacks::buffer_close)
+        } else {
+            None
+        };
+        let state = match close_callback {
+            Some(_) => callbacks::keep_state(self, BufferState::new(input, close)),
+            None => ptr::null(),
+        };
+
+        let ptr = unsafe {
+            call_attr!(
+                self.ptr,
+                buffer_new,
+                self.ptr,
+                cname.as_ptr(),
+                input_callback,
+                state,
+                ptr::null_mut(),
+                close_callback,
+                state,
+                ptr::null_mut()
+            )
+        };
+        if ptr.is_null() {
+            callbacks::drop_state(state);
+            return Err(Error::NullPointer("buffer_new"));
+        }
+
+        let buffer = Buffer::try_from_hdata(self.hdata_from_ptr("buffer", ptr as *mut c_void)?)?;
+        if buffer_type == BufferType::Free {
+            buffer.set("type", "free")?;
+        }
+        Ok(buffer)
+    }
+
+    fn buffer_from_ptr(&self, ptr: *mut ffi::t_gui_buffer) -> Option<Buffer> {
         if ptr.is_null() {
             return None;
         }
@@ -226,10 +986,47 @@ impl Plugin {
         Buffer::try_from_hdata(hdata).ok()
     }
 
+    pub fn buffer_search_main(&self) -> Option<Buffer> {
+        self.buffer_from_ptr(unsafe { call_attr!(self.ptr, buffer_search_main) })
+    }
+
+    // Find a buffer by the name of the plugin that owns it and its name, e.g.
+    // "irc" and "libera.#rust"
+    pub fn buffer_search(&self, plugin: &str, name: &str) -> Result<Option<Buffer>> {
+        let cplugin = CString::new(plugin)?;
+        let cname = CString::new(name)?;
+        Ok(self.buffer_from_ptr(unsafe {
+            call_attr!(self.ptr, buffer_search, cplugin.as_ptr(), cname.as_ptr())
+        }))
+    }
+
+    // Find a buffer by its full name, e.g. "irc.libera.#rust"
+    pub fn buffer_search_by_full_name(&self, full_name: &str) -> Result<Option<Buffer>> {
+        self.buffer_search("==", full_name)
+    }
+
+    pub fn current_buffer(&self) -> Option<Buffer> {
+        self.buffer_from_ptr(unsafe {
+            call_attr!(self.ptr, buffer_search, ptr::null(), ptr::null())
+        })
+    }
+
+    // Iterate over all buffers in the order they are numbered
+    pub fn buffers(&self) -> Result<BufferIterator> {
+        Ok(BufferIterator {
+            inner: self
+                .hdata_from_list("buffer", "gui_buffers")?
+                .try_iter_in("gui_buffers")?,
+        })
+    }
+
     fn hdata_ptr(&self, name: &str) -> Result<*mut ffi::t_hdata> {
-        let cname = CString::new(name).or(Err(()))?;
+        let cname = CString::new(name)?;
         let ptr = unsafe { call_attr!(self.ptr, hdata_get, self.ptr, cname.as_ptr()) };
-        Ok(try_ptr!(ptr))
+        if ptr.is_null() {
+            return Err(Error::NotFound(format!("hdata {}", name)));
+        }
+        Ok(ptr)
     }
 
     pub fn hdata_from_ptr(&self, name: &str, data_ptr: *mut c_void) -> Result<Hdata> {
@@ -238,9 +1035,42 @@ impl Plugin {
 
     pub fn hdata_from_list(&self, name: &str, list: &str) -> Result<Hdata> {
         let hdata_ptr = self.hdata_ptr(name)?;
-        let clist = CString::new(list).or(Err(()))?;
-        let data_ptr =
-            try_ptr!(unsafe { call_attr!(self.ptr, hdata_get_list, hdata_ptr, clist.as_ptr()) });
+        let clist = CString::new(list)?;
+        let data_ptr = unsafe { call_attr!(self.ptr, hdata_get_list, hdata_ptr, clist.as_ptr()) };
+        if data_ptr.is_null() {
+            return Err(Error::NotFound(format!("hdata list {}.{}", name, list)));
+        }
         Ok(Hdata::new(&self, hdata_ptr, data_ptr))
     }
+
+    pub fn hashtable_new(
+        &self,
+        key_type: HashtableType,
+        value_type: HashtableType,
+    ) -> Result<Hashtable> {
+        Hashtable::new(self, key_type, value_type)
+    }
+
+    pub fn hashtable_from_map<K, V>(&self, map: &HashMap<K, V>) -> Result<Hashtable>
+    where
+        K: Clone + Into<HashtableValue>,
+        V: Clone + Into<HashtableValue>,
+    {
+        let mut items = map
+            .iter()
+            .map(|(k, v)| (k.clone().into(), v.clone().into()));
+
+        // An empty map carries no type information so we fall back to strings
+        let first: Option<(HashtableValue, HashtableValue)> = items.next();
+        let (key_type, value_type) = match first {
+            Some((ref k, ref v)) => (k.get_type(), v.get_type()),
+            None => (HashtableType::Str, HashtableType::Str),
+        };
+
+        let hashtable = self.hashtable_new(key_type, value_type)?;
+        for (k, v) in first.into_iter().chain(items) {
+            hashtable.set(k, v)?;
+        }
+        Ok(hashtable)
+    }
 }
@@ -0,0 +1,390 @@
+use super::{Buffer, CallResult, Error, Result};
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+// The irc plugin shows away nicks using this color instead of the nick color
+static AWAY_COLOR: &str = "weechat.color.nicklist_away";
+
+// Groups and nicks are freed along with their buffer, so the buffer is
+// checked before every access
+#[derive(Clone, Debug)]
+pub struct NicklistGroup<'a> {
+    buffer: Buffer<'a>,
+    ptr: *mut ::ffi::t_gui_nick_group,
+}
+
+#[derive(Clone, Debug)]
+pub struct Nick<'a> {
+    buffer: Buffer<'a>,
+    ptr: *mut ::ffi::t_gui_nick,
+}
+
+#[derive(Clone, Debug)]
+pub enum NicklistItem<'a> {
+    Group(NicklistGroup<'a>),
+    Nick(Nick<'a>),
+}
+
+// Walks the nicklist tree depth first, starting with the root group. Nicks
+// come right after the group they belong to
+#[derive(Clone, Debug)]
+pub struct NicklistIterator<'a> {
+    buffer: Buffer<'a>,
+    group: *mut ::ffi::t_gui_nick_group,
+    nick: *mut ::ffi::t_gui_nick,
+    done: bool,
+}
+
+fn string_from_ptr<'a>(ptr: *const libc::c_char, property: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(Error::NotFound(format!("nicklist property {}", property)));
+    }
+    Ok(unsafe { CStr::from_ptr(ptr) }.to_str()?)
+}
+
+impl<'a> NicklistGroup<'a> {
+    pub fn get_string(&self, property: &str) -> Result<&'a str> {
+        self.buffer.validate()?;
+        let cproperty = CString::new(property)?;
+        string_from_ptr(
+            unsafe {
+                call_attr!(
+                    self.buffer.hdata.plugin.ptr,
+                    nicklist_group_get_string,
+                    self.buffer.ptr,
+                    self.ptr,
+                    cproperty.as_ptr()
+                )
+            },
+            property,
+        )
+    }
+
+    pub fn get_integer(&self, property: &str) -> Result<i32> {
+        self.buffer.validate()?;
+        let cproperty = CString::new(property)?;
+        Ok(unsafe {
+            call_attr!(
+                self.buffer.hdata.plugin.ptr,
+                nicklist_group_get_integer,
+                self.buffer.ptr,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        })
+    }
+
+    // Group names may be prefixed with a number to sort them, like "000|o"
+    pub fn name(&self) -> Result<&'a str> {
+        self.get_string("name")
+    }
+
+    pub fn color(&self) -> Result<&'a str> {
+        self.get_string("color")
+    }
+
+    pub fn is_visible(&self) -> Result<bool> {
+        Ok(self.get_integer("visible")? != 0)
+    }
+
+    // Depth of the group in the tree, the root group is at level 0
+    pub fn level(&self) -> Result<i32> {
+        self.get_integer("level")
+    }
+}
+
+impl<'a> Nick<'a> {
+    pub fn get_string(&self, property: &str) -> Result<&'a str> {
+        self.buffer.validate()?;
+        let cproperty = CString::new(property)?;
+        string_from_ptr(
+            unsafe {
+                call_attr!(
+                    self.buffer.hdata.plugin.ptr,
+                    nicklist_nick_get_string,
+                    self.buffer.ptr,
+                    self.ptr,
+                    cproperty.as_ptr()
+                )
+            },
+            property,
+        )
+    }
+
+    pub fn get_integer(&self, property: &str) -> Result<i32> {
+        self.buffer.validate()?;
+        let cproperty = CString::new(property)?;
+        Ok(unsafe {
+            call_attr!(
+                self.buffer.hdata.plugin.ptr,
+                nicklist_nick_get_integer,
+                self.buffer.ptr,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        })
+    }
+
+    pub fn name(&self) -> Result<&'a str> {
+        self.get_string("name")
+    }
+
+    pub fn color(&self) -> Result<&'a str> {
+        self.get_string("color")
+    }
+
+    // Mode prefix like "@" for IRC operators, or " " when there is none
+    pub fn prefix(&self) -> Result<&'a str> {
+        self.get_string("prefix")
+    }
+
+    pub fn prefix_color(&self) -> Result<&'a str> {
+        self.get_string("prefix_color")
+    }
+
+    pub fn is_visible(&self) -> Result<bool> {
+        Ok(self.get_integer("visible")? != 0)
+    }
+
+    // Only plugins that mark away nicks using the nicklist away color, like
+    // the irc plugin, are detected
+    pub fn is_away(&self) -> Result<bool> {
+        Ok(self.color()? == AWAY_COLOR)
+    }
+
+    pub fn group(&self) -> Result<NicklistGroup<'a>> {
+        self.buffer.validate()?;
+        let cproperty = CString::new("group")?;
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.buffer.hdata.plugin.ptr,
+                    nicklist_nick_get_pointer,
+                    self.buffer.ptr,
+                    self.ptr,
+                    cproperty.as_ptr()
+                )
+            },
+            "nicklist_nick_get_pointer"
+        );
+        Ok(NicklistGroup {
+            buffer: self.buffer.clone(),
+            ptr: ptr as *mut ::ffi::t_gui_nick_group,
+        })
+    }
+}
+
+impl<'a> Iterator for NicklistIterator<'a> {
+    type Item = NicklistItem<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The buffer is checked once when the iterator is created rather than
+        // for every nick, since that scans all buffers. The returned items
+        // check it again before they are read
+        if self.done {
+            return None;
+        }
+
+        unsafe {
+            call_attr!(
+                self.buffer.hdata.plugin.ptr,
+                nicklist_get_next_item,
+                self.buffer.ptr,
+                &mut self.group,
+                &mut self.nick
+            );
+        }
+
+        if !self.nick.is_null() {
+            Some(NicklistItem::Nick(Nick {
+                buffer: self.buffer.clone(),
+                ptr: self.nick,
+            }))
+        } else if !self.group.is_null() {
+            Some(NicklistItem::Group(NicklistGroup {
+                buffer: self.buffer.clone(),
+                ptr: self.group,
+            }))
+        } else {
+            self.done = true;
+            None
+        }
+    }
+}
+
+impl<'a> Buffer<'a> {
+    pub fn iter_nicklist(&self) -> Result<NicklistIterator<'a>> {
+        self.validate()?;
+        Ok(NicklistIterator {
+            buffer: self.clone(),
+            group: ptr::null_mut(),
+            nick: ptr::null_mut(),
+            done: false,
+        })
+    }
+
+    pub fn nicks(&self) -> Result<Vec<Nick<'a>>> {
+        Ok(self
+            .iter_nicklist()?
+            .filter_map(|item| match item {
+                NicklistItem::Nick(nick) => Some(nick),
+                NicklistItem::Group(_) => None,
+            })
+            .collect())
+    }
+
+    // Search for a group by name in the given group and its subgroups, or in
+    // the whole nicklist if no group is given
+    pub fn search_group(
+        &self,
+        from_group: Option<&NicklistGroup>,
+        name: &str,
+    ) -> Result<Option<NicklistGroup<'a>>> {
+        self.validate()?;
+        let cname = CString::new(name)?;
+        let ptr = unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                nicklist_search_group,
+                self.ptr,
+                from_group.map_or(ptr::null_mut(), |g| g.ptr),
+                cname.as_ptr()
+            )
+        };
+        if ptr.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(NicklistGroup {
+            buffer: self.clone(),
+            ptr,
+        }))
+    }
+
+    pub fn search_nick(
+        &self,
+        from_group: Option<&NicklistGroup>,
+        name: &str,
+    ) -> Result<Option<Nick<'a>>> {
+        self.validate()?;
+        let cname = CString::new(name)?;
+        let ptr = unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                nicklist_search_nick,
+                self.ptr,
+                from_group.map_or(ptr::null_mut(), |g| g.ptr),
+                cname.as_ptr()
+            )
+        };
+        if ptr.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(Nick {
+            buffer: self.clone(),
+            ptr,
+        }))
+    }
+
+    // Add a group below the given parent group, or below the root group if no
+    // parent is given. Colors are option names like "weechat.color.chat" or
+    // color names like "red"
+    pub fn add_group(
+        &self,
+        parent: Option<&NicklistGroup>,
+        name: &str,
+        color: &str,
+        visible: bool,
+    ) -> Result<NicklistGroup<'a>> {
+        self.validate()?;
+        let cname = CString::new(name)?;
+        let ccolor = CString::new(color)?;
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.hdata.plugin.ptr,
+                    nicklist_add_group,
+                    self.ptr,
+                    parent.map_or(ptr::null_mut(), |g| g.ptr),
+                    cname.as_ptr(),
+                    ccolor.as_ptr(),
+                    visible as i32
+                )
+            },
+            "nicklist_add_group"
+        );
+        Ok(NicklistGroup {
+            buffer: self.clone(),
+            ptr,
+        })
+    }
+
+    pub fn add_nick(
+        &self,
+        group: Option<&NicklistGroup>,
+        name: &str,
+        color: &str,
+        prefix: &str,
+        prefix_color: &str,
+        visible: bool,
+    ) -> Result<Nick<'a>> {
+        self.validate()?;
+        let cname = CString::new(name)?;
+        let ccolor = CString::new(color)?;
+        let cprefix = CString::new(prefix)?;
+        let cprefix_color = CString::new(prefix_color)?;
+        let ptr = try_ptr!(
+            unsafe {
+                call_attr!(
+                    self.hdata.plugin.ptr,
+                    nicklist_add_nick,
+                    self.ptr,
+                    group.map_or(ptr::null_mut(), |g| g.ptr),
+                    cname.as_ptr(),
+                    ccolor.as_ptr(),
+                    cprefix.as_ptr(),
+                    cprefix_color.as_ptr(),
+                    visible as i32
+                )
+            },
+            "nicklist_add_nick"
+        );
+        Ok(Nick {
+            buffer: self.clone(),
+            ptr,
+        })
+    }
+
+    // Remove a group along with all of its subgroups and nicks
+    pub fn remove_group(&self, group: NicklistGroup) -> CallResult {
+        self.validate()?;
+        unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                nicklist_remove_group,
+                self.ptr,
+                group.ptr
+            )
+        };
+        Ok(())
+    }
+
+    pub fn remove_nick(&self, nick: Nick) -> CallResult {
+        self.validate()?;
+        unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                nicklist_remove_nick,
+                self.ptr,
+                nick.ptr
+            )
+        };
+        Ok(())
+    }
+
+    // Remove all groups and nicks except the root group
+    pub fn clear_nicklist(&self) -> CallResult {
+        self.validate()?;
+        unsafe { call_attr!(self.hdata.plugin.ptr, nicklist_remove_all, self.ptr) };
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use super::{Error, Plugin};
+use backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe, PanicInfo};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+type PanicHook = Box<dyn Fn(&PanicInfo) + Sync + Send + 'static>;
+
+static HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+static PREVIOUS_HOOK: AtomicPtr<PanicHook> = AtomicPtr::new(ptr::null_mut());
+
+thread_local! {
+    static CATCH_DEPTH: Cell<usize> = Cell::new(0);
+    static LAST_PANIC: RefCell<Option<(String, String)>> = RefCell::new(None);
+}
+
+fn install_hook() {
+    if HOOK_INSTALLED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    // The hook is process wide, so panics outside of catch_panic are passed on
+    // to whatever hook was installed before us
+    let previous = Box::into_raw(Box::new(panic::take_hook()));
+    PREVIOUS_HOOK.store(previous, Ordering::SeqCst);
+
+    // The default hook prints to stderr which would garble the Weechat UI, so
+    // we stash the message and backtrace until the panic has been caught
+    panic::set_hook(Box::new(|info| {
+        if CATCH_DEPTH.with(|d| d.get()) == 0 {
+            let previous = PREVIOUS_HOOK.load(Ordering::SeqCst);
+            if !previous.is_null() {
+                unsafe { (*previous)(info) };
+            }
+            return;
+        }
+
+        let payload = info.payload();
+        let msg = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "Box<Any>".to_owned(),
+            },
+        };
+        let msg = match info.location() {
+            Some(l) => format!("{} at {}:{}", msg, l.file(), l.line()),
+            None => msg,
+        };
+        let backtrace = format!("{:?}", Backtrace::new());
+        LAST_PANIC.with(|p| *p.borrow_mut() = Some((msg, backtrace)));
+    }));
+}
+
+// Put back the panic hook that was installed before the first catch_panic.
+// Must be called when the plugin is unloaded since our hook would otherwise
+// outlive the code it points to
+pub fn restore_panic_hook() {
+    if !HOOK_INSTALLED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    let previous = PREVIOUS_HOOK.swap(ptr::null_mut(), Ordering::SeqCst);
+    if !previous.is_null() {
+        panic::set_hook(*unsafe { Box::from_raw(previous) });
+    }
+}
+
+// Run the given function, catching any panic that would otherwise unwind into
+// Weechat. Panics are printed with a backtrace to the core buffer and the
+// given fallback value is returned instead
+pub fn catch_panic<F, R>(plugin: &Plugin, fallback: R, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    install_hook();
+    CATCH_DEPTH.with(|d| d.set(d.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCH_DEPTH.with(|d| d.set(d.get() - 1));
+    match result {
+        Ok(r) => r,
+        Err(_) => {
+            let (msg, backtrace) = LAST_PANIC
+                .with(|p| p.borrow_mut().take())
+                .unwrap_or_else(|| ("unknown panic".to_owned(), String::new()));
+
+            // Printing could panic as well, in which case we give up
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                plugin.print_error(&Error::Panic(msg));
+                for line in backtrace.lines() {
+                    plugin.print(line);
+                }
+            }));
+            fallback
+        },
+    }
+}
@@ -0,0 +1,98 @@
+use super::{Hashtable, HashtableType, Plugin, Result};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+pub struct ProcessCommand {
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    stdin: Option<String>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcessOutput {
+    pub command: String,
+    // None if the process could not be started, timed out or was killed
+    pub return_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ProcessCommand {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_owned());
+        self
+    }
+
+    pub fn args<'a, I: IntoIterator<Item = &'a str>>(mut self, args: I) -> Self {
+        self.args.extend(args.into_iter().map(|a| a.to_owned()));
+        self
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn stdin(mut self, data: &str) -> Self {
+        self.stdin = Some(data.to_owned());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub(crate) fn get_stdin(&self) -> Option<&str> {
+        self.stdin.as_ref().map(|s| s.as_str())
+    }
+
+    pub(crate) fn get_timeout(&self) -> Duration {
+        self.timeout.unwrap_or_else(|| Duration::from_secs(0))
+    }
+
+    pub(crate) fn get_command(&self) -> &str {
+        // Weechat has no option for setting environment variables, so we let
+        // env(1) set them before executing the actual program
+        if self.env.is_empty() {
+            &self.program
+        } else {
+            "env"
+        }
+    }
+
+    pub(crate) fn to_options<'a>(&self, plugin: &'a Plugin) -> Result<Hashtable<'a>> {
+        let options = plugin.hashtable_new(HashtableType::Str, HashtableType::Str)?;
+
+        // Passing arguments through argN makes Weechat execute the program
+        // directly instead of going through the shell
+        let mut args: Vec<String> = self
+            .env
+            .iter()
+            .map(|&(ref k, ref v)| format!("{}={}", k, v))
+            .collect();
+        if !self.env.is_empty() {
+            args.push(self.program.clone());
+        }
+        args.extend(self.args.iter().cloned());
+
+        for (i, arg) in args.into_iter().enumerate() {
+            options.set(format!("arg{}", i + 1), arg)?;
+        }
+
+        if self.stdin.is_some() {
+            options.set("stdin", "1")?;
+        }
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,167 @@
+use super::{Buffer, CallResult, Error, Hdata, HdataIterator, Plugin, Result};
+use std::ffi::{c_void, CStr, CString};
+use std::ptr;
+
+#[derive(Clone, Debug)]
+pub struct Window<'a> {
+    hdata: Hdata<'a>,
+    ptr: *mut ::ffi::t_gui_window,
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowIterator<'a> {
+    inner: HdataIterator<'a>,
+}
+
+impl<'a> Window<'a> {
+    pub fn try_from_hdata(hdata: Hdata<'a>) -> Result<Self> {
+        let window = Self {
+            ptr: hdata.data_ptr as *mut ::ffi::t_gui_window,
+            hdata,
+        };
+        window.validate()?;
+        Ok(window)
+    }
+
+    // Windows are freed when they are closed or merged, so stored windows
+    // must be checked before use
+    pub fn is_valid(&self) -> bool {
+        match self.hdata.plugin.hdata_from_list("window", "gui_windows") {
+            Ok(window_list) => self.hdata.is_valid_in(&window_list),
+            Err(_) => false,
+        }
+    }
+
+    pub fn validate(&self) -> CallResult {
+        if !self.is_valid() {
+            return Err(Error::InvalidPointer("window".to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn get_integer(&self, property: &str) -> Result<i32> {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        Ok(unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                window_get_integer,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        })
+    }
+
+    pub fn get_string(&self, property: &str) -> Result<&'a str> {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        let value = unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                window_get_string,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        };
+        if value.is_null() {
+            return Err(Error::NotFound(format!("window property {}", property)));
+        }
+        Ok(unsafe { CStr::from_ptr(value) }.to_str()?)
+    }
+
+    pub fn get_pointer(&self, property: &str) -> Result<*mut c_void> {
+        self.validate()?;
+        let cproperty = CString::new(property)?;
+        Ok(unsafe {
+            call_attr!(
+                self.hdata.plugin.ptr,
+                window_get_pointer,
+                self.ptr,
+                cproperty.as_ptr()
+            )
+        })
+    }
+
+    // Windows are numbered from 1
+    pub fn number(&self) -> Result<i32> {
+        self.get_integer("number")
+    }
+
+    pub fn width(&self) -> Result<i32> {
+        self.get_integer("win_width")
+    }
+
+    pub fn height(&self) -> Result<i32> {
+        self.get_integer("win_height")
+    }
+
+    // The buffer displayed in this window
+    pub fn buffer(&self) -> Result<Buffer<'a>> {
+        let ptr = try_ptr!(self.get_pointer("buffer")?, "window_get_pointer");
+        Buffer::try_from_hdata(self.hdata.plugin.hdata_from_ptr("buffer", ptr)?)
+    }
+
+    pub fn is_current(&self) -> Result<bool> {
+        self.validate()?;
+        Ok(self.hdata.plugin.current_window()?.ptr == self.ptr)
+    }
+}
+
+impl<'a> Iterator for WindowIterator<'a> {
+    type Item = Window<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The inner iterator has already checked the window against
+        // gui_windows
+        self.inner.next().map(|hdata| Window {
+            ptr: hdata.data_ptr as *mut ::ffi::t_gui_window,
+            hdata,
+        })
+    }
+}
+
+impl<'a> Buffer<'a> {
+    // Check whether the buffer is displayed in any window
+    pub fn is_visible(&self) -> Result<bool> {
+        Ok(self.hdata.plugin.window_search_with_buffer(self)?.is_some())
+    }
+}
+
+impl Plugin {
+    fn window_from_ptr(&self, ptr: *mut ::ffi::t_gui_window) -> Result<Option<Window>> {
+        if ptr.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(Window::try_from_hdata(
+            self.hdata_from_ptr("window", ptr as *mut c_void)?,
+        )?))
+    }
+
+    pub fn current_window(&self) -> Result<Window> {
+        let ccurrent = CString::new("current")?;
+        let ptr = unsafe {
+            call_attr!(
+                self.ptr,
+                window_get_pointer,
+                ptr::null_mut(),
+                ccurrent.as_ptr()
+            )
+        };
+        self.window_from_ptr(ptr as *mut ::ffi::t_gui_window)?
+            .ok_or(Error::NullPointer("window_get_pointer"))
+    }
+
+    // Find the first window displaying the given buffer
+    pub fn window_search_with_buffer(&self, buffer: &Buffer) -> Result<Option<Window>> {
+        buffer.validate()?;
+        self.window_from_ptr(unsafe { call_attr!(self.ptr, window_search_with_buffer, buffer.ptr) })
+    }
+
+    pub fn windows(&self) -> Result<WindowIterator> {
+        Ok(WindowIterator {
+            inner: self
+                .hdata_from_list("window", "gui_windows")?
+                .try_iter_in("gui_windows")?,
+        })
+    }
+}
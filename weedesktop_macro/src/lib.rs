@@ -6,8 +6,11 @@ extern crate syn;
 
 use proc_macro::TokenStream;
 use std::ffi::CString;
-use syn::{Expr, Ident, ItemFn, LitInt, LitStr, LitByteStr, ItemStatic};
-use syn::export::{Span, ToTokens};
+use syn::{
+    Attribute, Data, DeriveInput, Expr, Fields, GenericParam, Ident, ItemFn, ItemStatic, Lifetime,
+    LifetimeDef, Lit, LitByteStr, LitInt, LitStr, Meta, NestedMeta,
+};
+use syn::export::{Span, ToTokens, TokenStream2};
 use syn::parse::{Parse, ParseStream, Result, Error};
 
 mod ffi;
@@ -110,10 +113,16 @@ pub fn plugin_init(_metadata: TokenStream, input: TokenStream) -> TokenStream {
 
         #[no_mangle]
         pub extern "C" fn weechat_plugin_init(ptr: *mut ffi::t_weechat_plugin, _argc: i32, _argv: *const *const u8) -> i32 {
-            match #init_fn_name(&weechat::Plugin::new(ptr)) {
-                Ok(()) => ffi::WEECHAT_RC_OK,
-                Err(()) => ffi::WEECHAT_RC_ERROR,
-            }
+            let plugin = weechat::Plugin::new(ptr);
+            weechat::catch_panic(&plugin, ffi::WEECHAT_RC_ERROR, || {
+                match #init_fn_name(&plugin) {
+                    Ok(()) => ffi::WEECHAT_RC_OK,
+                    Err(e) => {
+                        plugin.print_error(&e);
+                        ffi::WEECHAT_RC_ERROR
+                    },
+                }
+            })
         }
     })
 }
@@ -128,10 +137,127 @@ pub fn plugin_end(_metadata: TokenStream, input: TokenStream) -> TokenStream {
 
         #[no_mangle]
         pub extern "C" fn weechat_plugin_end(ptr: *mut ffi::t_weechat_plugin) -> i32 {
-            match #end_fn_name(&weechat::Plugin::new(ptr)) {
-                Ok(()) => ffi::WEECHAT_RC_OK,
-                Err(()) => ffi::WEECHAT_RC_ERROR,
+            let plugin = weechat::Plugin::new(ptr);
+            let rc = weechat::catch_panic(&plugin, ffi::WEECHAT_RC_ERROR, || {
+                match #end_fn_name(&plugin) {
+                    Ok(()) => ffi::WEECHAT_RC_OK,
+                    Err(e) => {
+                        plugin.print_error(&e);
+                        ffi::WEECHAT_RC_ERROR
+                    },
+                }
+            });
+            weechat::restore_panic_hook();
+            rc
+        }
+    })
+}
+
+// Find the value of name in #[hdata(name = "value")] attributes. Any other key
+// is an error, since it is most likely a typo or used in the wrong place
+fn hdata_attr(attrs: &[Attribute], name: &str) -> Result<Option<LitStr>> {
+    let mut found = None;
+    for attr in attrs {
+        if attr.path.segments.len() != 1 || attr.path.segments[0].ident != "hdata" {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "Expected #[hdata(key = \"value\")]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(ref name_value)) if name_value.ident == name => {
+                    match name_value.lit {
+                        Lit::Str(ref value) => found = Some(value.clone()),
+                        ref lit => return Err(Error::new_spanned(lit, "Expected a string")),
+                    }
+                },
+                NestedMeta::Meta(Meta::NameValue(ref name_value)) => {
+                    return Err(Error::new_spanned(
+                        &name_value.ident,
+                        format!("Unknown hdata attribute, expected `{}`", name),
+                    ))
+                },
+                nested => return Err(Error::new_spanned(nested, "Expected key = \"value\"")),
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn derive_from_hdata(input: DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let hdata_name = match hdata_attr(&input.attrs, "name")? {
+        Some(name) => name,
+        None => return Err(Error::new(ident.span(), "Missing #[hdata(name = \"...\")]")),
+    };
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(Error::new(ident.span(), "FromHdata requires named fields")),
+        },
+        _ => return Err(Error::new(ident.span(), "FromHdata can only be derived for structs")),
+    };
+
+    // Values borrow from the hdata, so we reuse the struct's lifetime if it
+    // has one and introduce a new one otherwise
+    let mut impl_generics = input.generics.clone();
+    let lifetime = match input.generics.lifetimes().next() {
+        Some(def) => def.lifetime.clone(),
+        None => {
+            let lifetime = Lifetime::new("'hdata", Span::call_site());
+            impl_generics
+                .params
+                .push(GenericParam::Lifetime(LifetimeDef::new(lifetime.clone())));
+            lifetime
+        },
+    };
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut validations = Vec::new();
+    let mut initializers = Vec::new();
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let var_name = match hdata_attr(&field.attrs, "rename")? {
+            Some(name) => name,
+            None => LitStr::new(&field_ident.to_string(), field_ident.span()),
+        };
+        validations.push(quote! {
+            ::weechat::validate_var::<#ty>(plugin, #hdata_name, #var_name)?;
+        });
+        initializers.push(quote! {
+            #field_ident: ::weechat::HdataField::from_hdata_var(hdata, #var_name)?
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::weechat::FromHdata<#lifetime> for #ident #ty_generics #where_clause {
+            const HDATA_NAME: &'static str = #hdata_name;
+
+            fn validate(plugin: &::weechat::Plugin) -> ::weechat::Result<()> {
+                #(#validations)*
+                Ok(())
+            }
+
+            fn from_hdata(hdata: &::weechat::Hdata<#lifetime>) -> ::weechat::Result<Self> {
+                Ok(#ident {
+                    #(#initializers,)*
+                })
             }
         }
     })
 }
+
+#[proc_macro_derive(FromHdata, attributes(hdata))]
+pub fn from_hdata(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match derive_from_hdata(input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}